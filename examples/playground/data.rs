@@ -110,6 +110,7 @@ impl Node {
                                 dir.children.insert(index, value);
                             }
                         }
+                        DropPosition::On(_) => return Err(value),
                     }
                     Ok(())
                 } else {
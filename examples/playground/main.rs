@@ -3,7 +3,9 @@ use std::env;
 
 use data::*;
 use egui::{Color32, DragValue, Id, Label, Layout, Response, Ui};
-use egui_ltreeview::{node::NodeBuilder, Action, RowLayout, TreeView, TreeViewBuilder, VLineStyle};
+use egui_ltreeview::{
+    node::NodeBuilder, Action, DropMarkerStyle, RowLayout, TreeView, TreeViewBuilder, VLineStyle,
+};
 use uuid::Uuid;
 
 fn main() -> Result<(), eframe::Error> {
@@ -37,6 +39,7 @@ struct Settings {
     override_indent: Option<f32>,
     vline_style: VLineStyle,
     row_layout: RowLayout,
+    drop_marker_style: DropMarkerStyle,
     fill_space_horizontal: bool,
     fill_space_vertical: bool,
     max_width_enabled: bool,
@@ -103,6 +106,7 @@ fn show_tree_view(ui: &mut Ui, app: &mut MyApp) -> Response {
         .override_indent(app.settings.override_indent)
         .vline_style(app.settings.vline_style)
         .row_layout(app.settings.row_layout)
+        .drop_marker_style(app.settings.drop_marker_style)
         .fill_space_horizontal(app.settings.fill_space_horizontal)
         .fill_space_vertical(app.settings.fill_space_vertical)
         .max_width(if app.settings.max_width_enabled {
@@ -155,6 +159,15 @@ fn show_tree_view(ui: &mut Ui, app: &mut MyApp) -> Response {
                 }
             }
             Action::Drag { .. } => (),
+            Action::MoveToRoot { .. } => (),
+            Action::DragToRoot { .. } => (),
+            Action::Collapsed(_) => (),
+            Action::Activate { .. } => (),
+            Action::Copy(_) => (),
+            Action::Cut(_) => (),
+            Action::Paste { .. } => (),
+            Action::NodeClicked { .. } => (),
+            Action::DropExternal { .. } => (),
         }
     }
     if app.settings.show_size {
@@ -333,6 +346,32 @@ fn show_settings(ui: &mut Ui, settings: &mut Settings) {
             });
         ui.end_row();
 
+        ui.label("Drop marker style");
+        egui::ComboBox::from_id_salt("drop marker style combo box")
+            .selected_text(match settings.drop_marker_style {
+                DropMarkerStyle::Line => "Line",
+                DropMarkerStyle::RowHighlight => "Row highlight",
+                DropMarkerStyle::Caret => "Caret",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(
+                    &mut settings.drop_marker_style,
+                    DropMarkerStyle::Line,
+                    "Line",
+                );
+                ui.selectable_value(
+                    &mut settings.drop_marker_style,
+                    DropMarkerStyle::RowHighlight,
+                    "Row highlight",
+                );
+                ui.selectable_value(
+                    &mut settings.drop_marker_style,
+                    DropMarkerStyle::Caret,
+                    "Caret",
+                );
+            });
+        ui.end_row();
+
         ui.label("fill horizontal");
         ui.checkbox(&mut settings.fill_space_horizontal, "");
         ui.end_row();
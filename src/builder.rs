@@ -1,12 +1,22 @@
+use std::sync::Arc;
+
 use egui::{
-    epaint::{self, RectShape},
+    emath::TSTransform,
+    epaint::{
+        self,
+        text::{LayoutJob, TextFormat, TextWrapping},
+        RectShape,
+    },
     layers::ShapeIdx,
-    pos2, vec2, Pos2, Rangef, Rect, Shape, Stroke, Ui, WidgetText,
+    pos2, vec2, Align2, Color32, Galley, Id, LayerId, Painter, PointerButton, Pos2, Rangef, Rect,
+    Shape, Stroke, TextStyle, Ui, UiBuilder, WidgetText,
 };
 
 use crate::{
     node::{DropQuarter, NodeBuilder},
-    DragState, DropPosition, NodeState, TreeViewData, TreeViewId, TreeViewSettings, VLineStyle,
+    Action, ActivateOn, ActivationMethod, CloserPosition, DragState, DropMarkerStyle, DropPosition,
+    DropPositionMask, GutterRow, NodeState, RowArea, TreeViewData, TreeViewId, TreeViewSettings,
+    TreeViewState, VLineStyle,
 };
 
 #[derive(Clone)]
@@ -17,6 +27,10 @@ struct DirectoryState<NodeIdType> {
     is_open: bool,
     /// Wether dropping on this or any of its child nodes is allowed.
     drop_forbidden: bool,
+    /// Wether dropping into this directory is allowed.
+    drop_allowed: bool,
+    /// The drop positions this directory accepts for its children.
+    allowed_drop_positions: DropPositionMask,
     /// The rectangle of the row.
     row_rect: Rect,
     /// The rectangle of the icon.
@@ -27,41 +41,183 @@ struct DirectoryState<NodeIdType> {
     indent_level: usize,
     /// If this dir was flattened.
     flattened: bool,
+    /// If the children of this dir are still being loaded.
+    loading: bool,
+    /// Number of child nodes that were added to this dir.
+    child_count: usize,
+    /// Number of children per page. `None` if all children are shown.
+    child_page_size: Option<usize>,
+    /// Number of children the directory has if it was set by the user.
+    known_child_count: Option<usize>,
+    /// Number of pages of children that are shown.
+    child_pages: usize,
+    /// Index of the node state of this directory in the new node states.
+    node_state_index: usize,
+    /// Top of the area of the children of this directory.
+    body_top: f32,
+    /// If this directory is animated closed and its children are
+    /// only shown for the animation.
+    closing: bool,
+    /// The running open or close animation of this directory.
+    animation: Option<BodyAnimation>,
+    /// Index of the row of this directory if its gutter should be painted.
+    gutter_row_index: Option<usize>,
+    /// If the drop preview should be shown after the children of this directory.
+    drop_preview_after: bool,
+}
+
+/// The open or close animation of the children of a directory.
+#[derive(Clone)]
+struct BodyAnimation {
+    /// Height of the children that is shown.
+    visible_height: f32,
+    /// Clip rect of the ui before the animation.
+    clip_rect: Rect,
+    /// Opacity of the ui before the animation.
+    opacity: f32,
+    /// Interaction clip rect before the animation.
+    interact_clip: Rect,
 }
 
 /// The builder used to construct the tree view.
 ///
 /// Use this to add directories or leaves to the tree.
-pub struct TreeViewBuilder<'ui, 'state, NodeIdType> {
+///
+/// To show multiple nodes at the top level of the tree, add them to a root directory
+/// that is hidden with [`TreeView::hide_root`](crate::TreeView::hide_root).
+///
+/// `Ctx` is the type of the context that is passed to the callbacks of the nodes
+/// when the tree is shown with [`TreeView::show_with`](crate::TreeView::show_with).
+pub struct TreeViewBuilder<'ui, 'state, NodeIdType, Ctx = ()> {
     ui: &'ui mut Ui,
     data: &'ui mut TreeViewData<'state, NodeIdType>,
+    ctx: &'ui mut Ctx,
+    settings: &'ui TreeViewSettings,
+    build: &'ui mut BuildState<NodeIdType>,
+}
+
+/// The state of a [`TreeViewBuilder`].
+///
+/// It outlives the builder that is passed to the user so that the tree can be
+/// finished once the user is done building it.
+pub(crate) struct BuildState<NodeIdType> {
     stack: Vec<DirectoryState<NodeIdType>>,
     background_idx: ShapeIdx,
     secondary_selection_idx: ShapeIdx,
-    settings: &'ui TreeViewSettings,
+    /// Top of the block of pinned nodes.
+    pinned_top: f32,
+    pinned_selection_idx: ShapeIdx,
+    /// The last sticky header and its row.
+    sticky_header: Option<(Arc<Galley>, Rect)>,
+    /// The last directory at the top level of the tree if nodes can be dropped into it.
+    root_drop_target: Option<NodeIdType>,
+    /// Number of visible rows that were added so far.
+    visible_rows: usize,
+    /// The layer the tree is shown on.
+    layer_id: LayerId,
+    /// Where the placeholder row for the dragged node is shown.
+    drop_preview: Option<(Option<NodeIdType>, DropPosition<NodeIdType>)>,
+    /// If the context menu was already shown this frame.
+    context_menu_shown: bool,
+    /// Vertical offset of the row that is currently shown from its position
+    /// while it slides there after a move.
+    row_offset: f32,
 }
 
-impl<'ui, 'state, NodeIdType: TreeViewId> TreeViewBuilder<'ui, 'state, NodeIdType> {
+impl<NodeIdType: TreeViewId> BuildState<NodeIdType> {
     pub(crate) fn new(
-        ui: &'ui mut Ui,
-        state: &'ui mut TreeViewData<'state, NodeIdType>,
-        settings: &'ui TreeViewSettings,
+        ui: &mut Ui,
+        state: &TreeViewData<NodeIdType>,
+        settings: &TreeViewSettings,
     ) -> Self {
+        // The placeholder row is shown where the node would have been dropped last frame.
+        // Placing it where it would be dropped this frame is not possible because
+        // the drop target is only known after the rows have been laid out.
+        let drop_preview = state
+            .peristant
+            .drop_target
+            .filter(|_| settings.drop_preview)
+            .filter(|(_, position)| !matches!(position, DropPosition::On(_)));
+        // Reserve space for the pinned nodes. They are shown when they are added to the tree.
+        let pinned_top = ui.cursor().top();
+        let pinned_selection_idx = ui.painter().add(Shape::Noop);
+        if state.peristant.shown_pinned().next().is_some() {
+            let item_spacing = ui.spacing().item_spacing.y;
+            let height: f32 = state
+                .peristant
+                .shown_pinned()
+                .map(|id| pinned_row_height(ui, state.peristant, id) + item_spacing)
+                .sum();
+            ui.allocate_space(vec2(0.0, height - item_spacing));
+            ui.separator();
+        }
         Self {
+            layer_id: ui.layer_id(),
             background_idx: ui.painter().add(Shape::Noop),
             secondary_selection_idx: ui.painter().add(Shape::Noop),
-            ui,
-            data: state,
+            pinned_top,
+            pinned_selection_idx,
             stack: Vec::new(),
+            sticky_header: None,
+            root_drop_target: None,
+            visible_rows: 0,
+            drop_preview,
+            context_menu_shown: false,
+            row_offset: 0.0,
+        }
+    }
+}
+
+impl<'ui, 'state, NodeIdType: TreeViewId, Ctx> TreeViewBuilder<'ui, 'state, NodeIdType, Ctx> {
+    pub(crate) fn new(
+        ui: &'ui mut Ui,
+        data: &'ui mut TreeViewData<'state, NodeIdType>,
+        settings: &'ui TreeViewSettings,
+        ctx: &'ui mut Ctx,
+        build: &'ui mut BuildState<NodeIdType>,
+    ) -> Self {
+        Self {
+            ui,
+            data,
+            ctx,
             settings,
+            build,
         }
     }
 
+    /// Close all directories that were left open and paint what depends on the whole tree.
+    pub(crate) fn finish(mut self) {
+        while !self.build.stack.is_empty() {
+            self.close_dir();
+        }
+        let tree_end = self.ui.min_rect().bottom();
+        self.root_drop_zone();
+        self.paint_sticky_header(tree_end);
+    }
+
     /// Get the current parent id if any.
     pub fn parent_id(&self) -> Option<NodeIdType> {
         self.parent_dir().map(|state| state.id)
     }
 
+    /// Whether or not the children of the directory with this id are shown.
+    ///
+    /// Use this after adding a directory to avoid creating its children when
+    /// they are not shown anyway. The children of a directory are not shown
+    /// if the directory or any of its parents is closed.
+    /// For a directory that was not added yet, this is the openness it had in the
+    /// previous frame and `true` for directories the tree view does not know.
+    pub fn is_open(&self, id: &NodeIdType) -> bool {
+        match self.build.stack.iter().rev().find(|dir| &dir.id == id) {
+            Some(dir) => dir.is_open,
+            None => self
+                .data
+                .peristant
+                .node_state_of(id)
+                .map_or(true, |node_state| node_state.open),
+        }
+    }
+
     /// Add a leaf to the tree.
     pub fn leaf(&mut self, id: NodeIdType, label: impl Into<WidgetText>) {
         let widget_text = label.into();
@@ -79,26 +235,260 @@ impl<'ui, 'state, NodeIdType: TreeViewId> TreeViewBuilder<'ui, 'state, NodeIdTyp
         }));
     }
 
+    /// Add a section header to the tree.
+    ///
+    /// A header spans the whole row and cannot be selected, dragged or
+    /// navigated to with the keyboard. Use it to group nodes into sections.
+    pub fn header(&mut self, label: impl Into<WidgetText>) {
+        self.header_internal(label.into(), false);
+    }
+
+    /// Add a section header that sticks to the top of the visible area
+    /// while its section is scrolled through.
+    ///
+    /// See [`Self::header`].
+    pub fn sticky_header(&mut self, label: impl Into<WidgetText>) {
+        self.header_internal(label.into(), true);
+    }
+
+    fn header_internal(&mut self, label: WidgetText, sticky: bool) {
+        if !self.parent_dir_is_open() {
+            return;
+        }
+        let galley = label.strong().into_galley(
+            self.ui,
+            Some(egui::TextWrapMode::Extend),
+            f32::INFINITY,
+            TextStyle::Body,
+        );
+        let item_spacing = self.ui.spacing().item_spacing;
+        let (rect, _) = self.ui.allocate_exact_size(
+            vec2(
+                galley.size().x + 2.0 * item_spacing.x,
+                galley.size().y.max(self.ui.spacing().interact_size.y),
+            ),
+            egui::Sense::hover(),
+        );
+        let mut row = rect.expand2(vec2(0.0, item_spacing.y * 0.5));
+        row.set_width(self.ui.available_width());
+
+        self.ui.painter().galley(
+            self.header_text_pos(row, &galley),
+            Arc::clone(&galley),
+            self.ui.visuals().strong_text_color(),
+        );
+
+        // The next header pushes the previous sticky header out of view.
+        self.paint_sticky_header(row.top());
+        if sticky {
+            self.build.sticky_header = Some((galley, row));
+        }
+    }
+
+    /// Paint the last sticky header at the top of the visible area if its
+    /// row was scrolled out of view. `section_end` is where its section ends.
+    fn paint_sticky_header(&mut self, section_end: f32) {
+        let Some((galley, row)) = self.build.sticky_header.take() else {
+            return;
+        };
+        let clip_top = self.ui.clip_rect().top();
+        if row.top() >= clip_top {
+            return;
+        }
+        let top = clip_top.min(section_end - row.height());
+        let row = row.translate(vec2(0.0, top - row.top()));
+        self.ui
+            .painter()
+            .rect_filled(row, 0.0, self.ui.visuals().panel_fill);
+        self.ui.painter().galley(
+            self.header_text_pos(row, &galley),
+            galley,
+            self.ui.visuals().strong_text_color(),
+        );
+    }
+
+    /// Position of the text of a header in its row.
+    fn header_text_pos(&self, row: Rect, galley: &Galley) -> Pos2 {
+        let item_spacing = self.ui.spacing().item_spacing;
+        let x = if self.settings.right_to_left {
+            row.right() - item_spacing.x - galley.size().x
+        } else {
+            row.left() + item_spacing.x
+        };
+        pos2(x, row.center().y - galley.size().y * 0.5)
+    }
+
+    /// Add many leaves to the current directory.
+    ///
+    /// This is faster than adding each leaf with [`Self::leaf`] because leaves that
+    /// are not shown skip creating their label.
+    pub fn leaves<L: Into<WidgetText>>(
+        &mut self,
+        leaves: impl IntoIterator<Item = (NodeIdType, L)>,
+    ) {
+        let leaves = leaves.into_iter();
+        self.data.new_node_states.reserve(leaves.size_hint().0);
+        let parent_open = self.parent_dir_is_open();
+        for (id, label) in leaves {
+            // Pinned leaves are shown in the pinned block even if they are hidden here.
+            if (parent_open && !self.parent_dir_page_full()) || self.data.peristant.is_pinned(id) {
+                self.leaf(id, label);
+            } else {
+                // The leaf is not shown so it does not need a label.
+                self.node(NodeBuilder::leaf(id));
+            }
+        }
+    }
+
+    /// Add a directory to the tree and add its children in the `add_children` closure.
+    ///
+    /// The directory is closed when the closure returns. Any directory that was
+    /// opened inside the closure but not closed is closed as well.
+    pub fn dir_scoped<R>(
+        &mut self,
+        id: NodeIdType,
+        label: impl Into<WidgetText>,
+        add_children: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let depth = self.build.stack.len();
+        self.dir(id, label);
+        let result = add_children(self);
+        while self.build.stack.len() > depth {
+            self.close_dir();
+        }
+        result
+    }
+
+    /// Add a node from a flat list of nodes in pre-order.
+    ///
+    /// `depth` is the number of ancestors of the node. Directories are closed
+    /// automatically when the depth of the next node is not deeper than the depth
+    /// of the directory. Directories that are still open when the tree is done
+    /// building are closed as well.
+    ///
+    /// A node can be at most one level deeper than the previous node and
+    /// only if the previous node is a directory.
+    ///
+    /// Returns the number of following nodes that can be skipped because they are
+    /// descendants of a directory whose children are not shown. This is only known
+    /// for directories with a [`children_hint`](NodeBuilder::children_hint),
+    /// otherwise `0` is returned. Skipped nodes are not known to the tree view.
+    pub fn flat_node(&mut self, depth: usize, node: NodeBuilder<NodeIdType, Ctx>) -> usize {
+        while self.build.stack.len() > depth {
+            self.close_dir();
+        }
+        let children_hint = node.is_dir.then_some(node.children_hint).flatten();
+        self.node(node);
+        match children_hint {
+            Some(count) if !self.parent_dir_is_open() => count,
+            _ => 0,
+        }
+    }
+
+    /// Add a leaf with a plain text label to the tree.
+    ///
+    /// Unlike [`Self::leaf`], the laid out text is cached by the tree view and
+    /// only laid out again when the text, the font or the width it is wrapped at changes.
+    pub fn leaf_str(&mut self, id: NodeIdType, label: &str) {
+        let mut label = self.cached_label(id, label);
+        self.node(NodeBuilder::leaf(id).label(|ui| label.show(ui)));
+        self.cache_label(id, label);
+    }
+
+    /// Add a directory with a plain text label to the tree.
+    /// Must call [Self::close_dir] to close the directory.
+    ///
+    /// Unlike [`Self::dir`], the laid out text is cached by the tree view and
+    /// only laid out again when the text, the font or the width it is wrapped at changes.
+    pub fn dir_str(&mut self, id: NodeIdType, label: &str) {
+        let mut label = self.cached_label(id, label);
+        self.node(NodeBuilder::dir(id).label(|ui| label.show(ui)));
+        self.cache_label(id, label);
+    }
+
+    /// Get the label of a node with the galley that was cached for it.
+    fn cached_label<'a>(&mut self, id: NodeIdType, text: &'a str) -> CachedLabel<'a> {
+        CachedLabel {
+            text,
+            galley: self.data.peristant.label_galleys.remove(&id),
+        }
+    }
+
+    /// Keep the galley of a label for the next frame.
+    ///
+    /// The label is only laid out if the node was shown, otherwise the previous galley is kept.
+    fn cache_label(&mut self, id: NodeIdType, label: CachedLabel) {
+        if let Some(galley) = label.galley {
+            self.data.new_label_galleys.insert(id, galley);
+        }
+    }
+
     /// Close the current directory.
     pub fn close_dir(&mut self) {
-        let Some(current_dir) = self.stack.pop() else {
+        let Some(mut current_dir) = self.build.stack.pop() else {
             return;
         };
 
+        if current_dir.is_open
+            && self.build.drop_preview == Some((Some(current_dir.id), DropPosition::Last))
+        {
+            self.drop_preview_row(current_dir.indent_level);
+        }
+        self.show_more_row(&mut current_dir);
+        self.loading_row(&mut current_dir);
+        self.empty_dir_placeholder(&mut current_dir);
+        if let Some(row_index) = current_dir.gutter_row_index {
+            self.paint_gutter(
+                current_dir.row_rect,
+                GutterRow {
+                    row_index,
+                    depth: current_dir.indent_level - 1,
+                    child_count: Some(
+                        current_dir
+                            .child_count
+                            .max(current_dir.known_child_count.unwrap_or(0)),
+                    ),
+                },
+            );
+        }
+        if self.build.stack.is_empty() {
+            let drop_allowed = current_dir.drop_allowed
+                && !current_dir.drop_forbidden
+                && !self.settings.drag_reorder_only
+                && current_dir
+                    .allowed_drop_positions
+                    .contains(DropPositionMask::INTO)
+                && self.drop_depth_allowed(1);
+            self.build.root_drop_target = drop_allowed.then_some(current_dir.id);
+        }
+
         // Draw the drop marker over the entire dir if it is the target.
-        if let Some((drop_parent, DropPosition::Last)) = &self.data.drop {
-            if drop_parent == &current_dir.id {
+        // A flattened dir has no row to draw the marker over.
+        if let Some((drop_parent, drop_position)) = &self.data.drop {
+            let highlight_dir = match drop_position {
+                DropPosition::Last => true,
+                DropPosition::On(_) => false,
+                _ => self.settings.drop_marker_style == DropMarkerStyle::RowHighlight,
+            };
+            if highlight_dir && *drop_parent == Some(current_dir.id) && !current_dir.flattened {
                 let mut rect = current_dir.row_rect;
                 *rect.bottom_mut() =
                     self.ui.cursor().top() - self.ui.spacing().item_spacing.y * 0.5;
+                let fill = RectShape::new(
+                    rect,
+                    self.ui.visuals().widgets.active.rounding,
+                    self.settings
+                        .drop_marker_color
+                        .unwrap_or(self.ui.visuals().selection.bg_fill)
+                        .linear_multiply(0.5),
+                    Stroke::NONE,
+                );
                 self.ui.painter().set(
                     self.data.drop_marker_idx,
-                    RectShape::new(
-                        rect,
-                        self.ui.visuals().widgets.active.rounding,
-                        self.ui.visuals().selection.bg_fill.linear_multiply(0.5),
-                        Stroke::NONE,
-                    ),
+                    Shape::Vec(vec![
+                        fill.into(),
+                        self.drop_target_dir_outline(&current_dir.row_rect),
+                    ]),
                 );
             }
         }
@@ -129,7 +519,7 @@ impl<'ui, 'state, NodeIdType: TreeViewId> TreeViewBuilder<'ui, 'state, NodeIdTyp
             if matches!(self.settings.vline_style, VLineStyle::Hook) {
                 for child_pos in current_dir.child_node_positions.iter() {
                     let p1 = pos2(top.x, child_pos.y);
-                    let p2 = *child_pos + vec2(-2.0, 0.0);
+                    let p2 = *child_pos + vec2(self.text_dir() * -2.0, 0.0);
                     self.ui
                         .painter()
                         .line_segment([p1, p2], self.ui.visuals().widgets.noninteractive.bg_stroke);
@@ -139,58 +529,208 @@ impl<'ui, 'state, NodeIdType: TreeViewId> TreeViewBuilder<'ui, 'state, NodeIdTyp
 
         // Add child markers to next dir if this one was flattened.
         if current_dir.flattened {
-            if let Some(parent_dir) = self.stack.last_mut() {
+            if let Some(parent_dir) = self.build.stack.last_mut() {
                 parent_dir
                     .child_node_positions
                     .extend(current_dir.child_node_positions);
             }
         }
+
+        // Remember the height of the children to animate the directory.
+        if current_dir.is_open {
+            let body_height = self.ui.cursor().top() - current_dir.body_top;
+            self.data.new_node_states[current_dir.node_state_index].body_height = body_height;
+
+            if let Some(animation) = current_dir.animation {
+                self.ui.set_clip_rect(animation.clip_rect);
+                self.ui.set_opacity(animation.opacity);
+                self.data.interact_clip = animation.interact_clip;
+                // Let the next nodes cover the hidden part of the children.
+                self.ui.add_space(animation.visible_height - body_height);
+            }
+        }
+
+        if current_dir.drop_preview_after {
+            self.drop_preview_row(self.get_indent_level());
+        }
     }
 
     /// Add a node to the tree.
-    pub fn node(&mut self, mut node: NodeBuilder<NodeIdType>) {
-        let mut open = self
+    pub fn node(&mut self, mut node: NodeBuilder<NodeIdType, Ctx>) {
+        // The root is the first node of the tree. Hiding it works the same
+        // as flattening it.
+        if self.settings.hide_root && node.is_dir && self.data.new_node_states.is_empty() {
+            node.flatten = true;
+        }
+        // Only the last node at the top level can be the target of the root drop zone.
+        if self.build.stack.is_empty() {
+            self.build.root_drop_target = None;
+        }
+        if let Some(payload) = node.payload.take() {
+            self.data.payloads.insert(node.id, payload);
+        }
+
+        // A snapshot may have opened or closed the directory before it was added.
+        let pending_open = self
             .data
             .peristant
-            .node_state_of(&node.id)
-            .map(|node_state| node_state.open)
-            .unwrap_or(node.default_open);
+            .pending_openness
+            .remove(&node.id)
+            .filter(|_| node.is_dir && !node.openness_locked());
+        let node_state = self.data.peristant.node_state_of(&node.id);
+        let mut open = node.always_open
+            || node_state
+                .map(|node_state| node_state.open)
+                .or(pending_open)
+                .unwrap_or(node.default_open);
+        let child_pages = node_state.map_or(1, |node_state| node_state.child_pages);
+        let mut row_height = node_state.map_or(0.0, |node_state| node_state.row_height);
+        let body_height = node_state.map_or(0.0, |node_state| node_state.body_height);
+        let previous_top = node_state.and_then(|node_state| node_state.row_top);
+        let was_open = open;
 
-        let (row, closer) = if self.parent_dir_is_open() && !node.flatten {
-            node.set_is_open(open);
-            let (row, closer) = self.node_internal(&mut node);
+        // Nodes past the current page of their parent directory are not shown.
+        // Invisible nodes do not take up space on the page.
+        let parent_shown = node.visible && self.parent_dir_is_open() && !self.add_child_to_page();
+        let parent_id = self.parent_id();
+        let preview_at =
+            |position| parent_shown && self.build.drop_preview == Some((parent_id, position));
+        let drop_preview_before = preview_at(DropPosition::Before(node.id));
+        let drop_preview_after = preview_at(DropPosition::After(node.id));
+        if drop_preview_before {
+            self.drop_preview_row(self.get_indent_level());
+        }
 
-            if let Some(closer) = closer {
-                let closer_interaction = self.data.interact(&closer);
-                if closer_interaction.clicked {
-                    open = !open;
-                    self.data.peristant.selected = Some(node.id);
-                }
+        let visible = parent_shown && !node.flatten && !self.parent_dir_closing();
+        let row_index = self.build.visible_rows;
+        if visible {
+            self.build.visible_rows += 1;
+        }
+        let mut gutter_row_index = None;
+
+        // `anchor` is the rect the vline of a directory starts from.
+        let culled = parent_shown && !node.flatten && self.can_cull(&node, row_height);
+        let (row, anchor) = if culled {
+            (self.culled_row(row_height), Rect::NOTHING)
+        } else if parent_shown && !node.flatten {
+            node.set_is_open(open);
+            let (row, closer, content) = self.node_relocated(&mut node, previous_top);
+            self.data.stats.nodes_rendered += 1;
+            row_height = row.height();
+            if self.data.peristant.scroll_to_node == Some(node.id) {
+                let animation = self
+                    .settings
+                    .selection_scroll_animation
+                    .unwrap_or(self.ui.style().scroll_animation);
+                self.ui.scroll_to_rect_animation(
+                    row,
+                    self.settings.selection_scroll_align,
+                    animation,
+                );
+                self.data.peristant.scroll_to_node = None;
             }
 
-            let row_interaction = self.data.interact(&row);
-            if row_interaction.double_clicked {
-                open = !open;
+            self.row_interaction(&node, row, closer, content, &mut open);
+            if node.is_dir
+                && !open
+                && self.settings.partial_selection_marker
+                && self.data.peristant.contains_selection(node.id)
+            {
+                // Show that the closed directory hides the selected node.
+                let marker = Rect::from_center_size(
+                    self.start_of(row) + vec2(self.text_dir() * 1.5, 0.0),
+                    vec2(3.0, row.height()),
+                );
+                self.ui
+                    .painter()
+                    .rect_filled(marker, 0.0, self.ui.visuals().selection.bg_fill);
             }
-            (row, closer)
+            // The gutter of a directory shows its child count which is only known
+            // once the directory is closed.
+            if node.is_dir {
+                gutter_row_index = Some(row_index);
+            } else {
+                self.paint_gutter(
+                    row,
+                    GutterRow {
+                        row_index,
+                        depth: self.get_indent_level(),
+                        child_count: None,
+                    },
+                );
+            }
+            let anchor = match closer {
+                Some(closer) if self.settings.closer_position == CloserPosition::Left => closer,
+                _ => Rect::from_center_size(self.start_of(content), vec2(0.0, content.height())),
+            };
+            (row, anchor)
         } else {
-            (Rect::NOTHING, Some(Rect::NOTHING))
+            (Rect::NOTHING, Rect::NOTHING)
         };
 
+        if parent_shown && !node.flatten && self.data.is_selected(&node.id) {
+            self.data.selected_row = Some(row.y_range());
+        }
+
+        if node.visible && !node.flatten {
+            self.pinned_row(&mut node, &mut open, parent_shown && !culled);
+        }
+
+        if node.is_dir && open && !was_open {
+            self.data.opened_dirs.push(node.id);
+        }
+
+        let node_state_index = self.data.new_node_states.len();
         self.data.new_node_states.push(NodeState {
             id: node.id,
             parent_id: self.parent_id(),
+            is_dir: node.is_dir,
             open,
-            visible: self.parent_dir_is_open() && !node.flatten,
+            visible,
+            activatable: node.activatable,
+            child_pages,
+            row_height,
+            row_rect: if parent_shown && !node.flatten {
+                row
+            } else {
+                Rect::NOTHING
+            },
+            row_top: (parent_shown && !node.flatten).then(|| row.top() - self.build.pinned_top),
+            body_height,
+            focusable: node.focusable,
+            kind: node.kind,
+            openness_locked: node.openness_locked(),
+            drop_allowed: node.drop_allowed,
+            allowed_drop_positions: node.allowed_drop_positions,
         });
 
         if node.is_dir {
-            self.stack.push(DirectoryState {
-                is_open: self.parent_dir_is_open() && open,
+            let openness = if parent_shown && !node.flatten && self.animate_dirs() {
+                self.ui.ctx().animate_bool_with_time(
+                    Id::new(node.id).with("tree view dir body"),
+                    open,
+                    self.settings.dir_animation_duration,
+                )
+            } else if open {
+                1.0
+            } else {
+                0.0
+            };
+            // Without knowing the height of the children there is nothing to animate.
+            let animation = (openness > 0.0 && openness < 1.0 && body_height > 0.0)
+                .then(|| self.start_body_animation(openness * body_height, openness));
+            self.build.stack.push(DirectoryState {
+                is_open: parent_shown && openness > 0.0,
+                closing: self.parent_dir_closing() || (!open && openness > 0.0),
+                node_state_index,
+                body_top: self.ui.cursor().top(),
+                animation,
                 id: node.id,
                 drop_forbidden: self.parent_dir_drop_forbidden() || self.data.is_dragged(&node.id),
+                drop_allowed: node.drop_allowed,
+                allowed_drop_positions: node.allowed_drop_positions,
                 row_rect: row,
-                icon_rect: closer.expect("Closer response should be availabel for dirs"),
+                icon_rect: anchor,
                 child_node_positions: Vec::new(),
                 indent_level: if node.flatten {
                     self.get_indent_level()
@@ -198,56 +738,668 @@ impl<'ui, 'state, NodeIdType: TreeViewId> TreeViewBuilder<'ui, 'state, NodeIdTyp
                     self.get_indent_level() + 1
                 },
                 flattened: node.flatten,
+                loading: node.loading,
+                child_count: 0,
+                child_page_size: node.child_page_size,
+                known_child_count: node.child_count,
+                child_pages,
+                gutter_row_index,
+                drop_preview_after,
+            });
+            if parent_shown
+                && open
+                && self.build.drop_preview == Some((Some(node.id), DropPosition::First))
+            {
+                self.drop_preview_row(self.get_indent_level());
+            }
+        } else if drop_preview_after {
+            self.drop_preview_row(self.get_indent_level());
+        }
+    }
+
+    /// Show the node and slide it from where it was shown before a node was moved.
+    fn node_relocated(
+        &mut self,
+        node: &mut NodeBuilder<NodeIdType, Ctx>,
+        previous_top: Option<f32>,
+    ) -> (Rect, Option<Rect>, Rect) {
+        let offset = self.relocation_offset(&node.id, previous_top);
+        if offset == 0.0 {
+            return self.node_internal(node);
+        }
+        // Move the shapes of the row to the animated position once it is shown.
+        // The selection background is painted before the row and is moved
+        // through the row offset instead.
+        self.build.row_offset = offset;
+        let start = self.ui.painter().add(Shape::Noop);
+        let result = self.node_internal(node);
+        let end = self.ui.painter().add(Shape::Noop);
+        self.build.row_offset = 0.0;
+        self.ui.ctx().graphics_mut(|graphics| {
+            graphics.entry(self.ui.layer_id()).transform_range(
+                start,
+                end,
+                TSTransform::from_translation(vec2(0.0, offset)),
+            );
+        });
+        result
+    }
+
+    /// How far the row of the node is away from its animated position.
+    ///
+    /// Rows only slide when they were shown at a different position before
+    /// a node was moved, so that scrolling and opening directories do not
+    /// animate the rows.
+    fn relocation_offset(&mut self, id: &NodeIdType, previous_top: Option<f32>) -> f32 {
+        if !self.settings.animate_moves || !self.data.animations_enabled {
+            return 0.0;
+        }
+        let now = self.ui.input(|i| i.time);
+        let relocations = &mut self.data.peristant.relocations;
+        if self.data.peristant.moved {
+            let top = self.ui.cursor().top() - self.build.pinned_top;
+            if let Some(previous_top) = previous_top.filter(|prev| (prev - top).abs() >= 0.5) {
+                relocations.insert(*id, (previous_top - top, now));
+            }
+        }
+        let Some(&(start_offset, start_time)) = relocations.get(id) else {
+            return 0.0;
+        };
+        let t = ((now - start_time) / self.settings.move_animation_duration as f64) as f32;
+        if t >= 1.0 {
+            relocations.remove(id);
+            return 0.0;
+        }
+        self.ui.ctx().request_repaint();
+        start_offset * (1.0 - t)
+    }
+
+    /// The point where the content of `rect` starts in the text direction of the rows.
+    fn start_of(&self, rect: Rect) -> Pos2 {
+        if self.settings.right_to_left {
+            rect.right_center()
+        } else {
+            rect.left_center()
+        }
+    }
+
+    /// `1.0` if the rows are laid out left-to-right and `-1.0` if they are right-to-left.
+    fn text_dir(&self) -> f32 {
+        if self.settings.right_to_left {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Painter for the layer of the tree, even while a row is shown on its own layer.
+    fn tree_painter(&self) -> Painter {
+        self.ui.painter().clone().with_layer_id(self.build.layer_id)
+    }
+
+    /// The part of a row that can be clicked to interact with the node.
+    ///
+    /// The gutter is not part of it so that its widgets do not select the node.
+    fn row_hit_area(&self, mut row: Rect) -> Rect {
+        if let Some(gutter) = &self.settings.gutter {
+            let gutter_width = self.ui.spacing().item_spacing.x + gutter.width;
+            if self.settings.right_to_left {
+                row.max.x -= gutter_width;
+            } else {
+                row.min.x += gutter_width;
+            }
+        }
+        row
+    }
+
+    /// Show the placeholder row for the dragged node.
+    fn drop_preview_row(&mut self, indent_level: usize) {
+        let spacing = self.ui.spacing().item_spacing;
+        let row_height = self
+            .data
+            .peristant
+            .dragged
+            .as_ref()
+            .and_then(|drag_state| self.data.peristant.row_height_of(drag_state.node_id))
+            .unwrap_or(self.ui.spacing().interact_size.y + spacing.y);
+        let (rect, _) = self.ui.allocate_exact_size(
+            vec2(self.ui.available_width(), row_height - spacing.y),
+            egui::Sense::hover(),
+        );
+        let row = rect.expand2(vec2(0.0, spacing.y * 0.5));
+
+        let indent_width = self
+            .settings
+            .override_indent
+            .unwrap_or(self.ui.spacing().indent);
+        let gutter_width = self
+            .settings
+            .gutter
+            .as_ref()
+            .map_or(0.0, |gutter| gutter.width);
+        let mut placeholder = rect;
+        let indent = spacing.x + gutter_width + indent_level as f32 * indent_width;
+        if self.settings.right_to_left {
+            placeholder.max.x -= indent;
+        } else {
+            placeholder.min.x += indent;
+        }
+        self.ui.painter().rect_filled(
+            placeholder,
+            self.ui.visuals().widgets.active.rounding,
+            self.ui.visuals().selection.bg_fill.linear_multiply(0.4),
+        );
+
+        // The placeholder moves the rows so it has to keep the drop target
+        // while the pointer is over it.
+        if self.data.drag_valid() && self.data.drop_pos().is_some_and(|pos| row.contains(pos)) {
+            self.data.drop = self.build.drop_preview;
+        }
+    }
+
+    /// Paint the text of the gutter for a row.
+    fn paint_gutter(&self, row: Rect, gutter_row: GutterRow) {
+        let Some(gutter) = &self.settings.gutter else {
+            return;
+        };
+        let Some(text) = gutter.text.as_ref().and_then(|text| text(&gutter_row)) else {
+            return;
+        };
+        let (pos, align) = if self.settings.right_to_left {
+            (
+                pos2(row.right() - gutter.width, row.center().y),
+                Align2::LEFT_CENTER,
+            )
+        } else {
+            (
+                pos2(row.left() + gutter.width, row.center().y),
+                Align2::RIGHT_CENTER,
+            )
+        };
+        self.ui.painter().text(
+            pos,
+            align,
+            text,
+            TextStyle::Small.resolve(self.ui.style()),
+            self.ui.visuals().weak_text_color(),
+        );
+    }
+
+    fn animate_dirs(&self) -> bool {
+        self.settings.animate_dirs && self.data.animations_enabled
+    }
+
+    /// Only show the top part of the children that follow and fade them.
+    fn start_body_animation(&mut self, visible_height: f32, openness: f32) -> BodyAnimation {
+        let animation = BodyAnimation {
+            visible_height,
+            clip_rect: self.ui.clip_rect(),
+            opacity: self.ui.opacity(),
+            interact_clip: self.data.interact_clip,
+        };
+        let visible_rect = Rect::everything_above(self.ui.cursor().top() + visible_height);
+        self.ui
+            .set_clip_rect(animation.clip_rect.intersect(visible_rect));
+        self.ui.multiply_opacity(openness);
+        self.data.interact_clip = animation.interact_clip.intersect(visible_rect);
+        animation
+    }
+
+    /// Whether or not the row of a node can be skipped because it is outside
+    /// the visible area of the scroll area.
+    fn can_cull(&self, node: &NodeBuilder<NodeIdType, Ctx>, row_height: f32) -> bool {
+        if !self.settings.scroll
+            || node.is_dir
+            || row_height <= 0.0
+            || self.data.peristant.scroll_to_node == Some(node.id)
+            || self.data.is_dragged(&node.id)
+        {
+            return false;
+        }
+        let top = self.ui.cursor().top() - self.ui.spacing().item_spacing.y * 0.5;
+        let clip_rect = self.ui.clip_rect();
+        top + row_height < clip_rect.top() || top > clip_rect.bottom()
+    }
+
+    /// Take up the space of a row without drawing it.
+    ///
+    /// Returns the rect of the row.
+    fn culled_row(&mut self, row_height: f32) -> Rect {
+        self.data.stats.nodes_culled += 1;
+        let top = self.ui.cursor().top() - self.ui.spacing().item_spacing.y * 0.5;
+        let row = Rect::from_min_size(
+            pos2(self.ui.cursor().left(), top),
+            vec2(self.ui.available_width(), row_height),
+        );
+        self.ui
+            .allocate_space(vec2(0.0, row_height - self.ui.spacing().item_spacing.y));
+        // The row is not visible so the exact horizontal position of the
+        // marker for the vline does not matter.
+        let left = self
+            .parent_dir()
+            .map_or(self.ui.cursor().left(), |dir| dir.icon_rect.right());
+        self.push_child_node_position(pos2(left, row.center().y));
+        row
+    }
+
+    /// React to clicks on the row or closer of a node.
+    fn row_interaction(
+        &mut self,
+        node: &NodeBuilder<NodeIdType, Ctx>,
+        row: Rect,
+        closer: Option<Rect>,
+        content: Rect,
+        open: &mut bool,
+    ) {
+        let toggleable = !node.openness_locked();
+        let mut closer_clicked = false;
+        if let Some(closer) = closer {
+            let closer_interaction = self
+                .data
+                .interact(&closer.expand(self.settings.closer_hit_padding));
+            if closer_interaction.clicked {
+                if toggleable {
+                    *open = !*open;
+                }
+                closer_clicked = true;
+                if self.settings.interactive && self.data.peristant.can_select(node.id) {
+                    self.data.peristant.selected = Some(node.id);
+                }
+            }
+        }
+
+        let activate_on = if self.settings.interactive {
+            self.settings.activate_on
+        } else {
+            ActivateOn::NONE
+        };
+        let row_interaction = self.data.interact(&self.row_hit_area(row));
+        let activation_interaction = match self.settings.activation_hit_area {
+            RowArea::FullRow => row_interaction,
+            RowArea::LabelOnly => self
+                .data
+                .interact(&Rect::from_x_y_ranges(content.x_range(), row.y_range())),
+        };
+        if row_interaction.clicked
+            && !closer_clicked
+            && node.is_dir
+            && toggleable
+            && self.settings.toggle_on_row_click
+        {
+            *open = !*open;
+        }
+        if activation_interaction.clicked
+            && !closer_clicked
+            && node.activatable
+            && activate_on.contains(ActivateOn::SINGLE_CLICK)
+        {
+            self.data.actions.push(Action::Activate {
+                id: node.id,
+                method: ActivationMethod::SingleClick,
+            });
+        }
+        if row_interaction.double_clicked {
+            let activate = node.activatable
+                && activate_on.contains(ActivateOn::DOUBLE_CLICK)
+                && activation_interaction.double_clicked;
+            // Each click of the double click has already toggled the directory.
+            let toggled_by_click = node.is_dir && self.settings.toggle_on_row_click;
+            if toggleable
+                && !toggled_by_click
+                && (!activate || self.settings.double_click_toggles_activatable)
+            {
+                *open = !*open;
+            }
+            if activate {
+                self.data.actions.push(Action::Activate {
+                    id: node.id,
+                    method: ActivationMethod::DoubleClick,
+                });
+            }
+        }
+    }
+
+    /// Show the node again in the pinned block if it is pinned.
+    ///
+    /// `row_shown` is whether the node was also shown at its place in the tree.
+    fn pinned_row(
+        &mut self,
+        node: &mut NodeBuilder<NodeIdType, Ctx>,
+        open: &mut bool,
+        row_shown: bool,
+    ) {
+        // The space for the pinned rows was reserved before the nodes were added.
+        // Only the nodes the space was reserved for get a row.
+        let Some(slot) = self
+            .data
+            .peristant
+            .shown_pinned()
+            .position(|id| id == &node.id)
+        else {
+            return;
+        };
+        let item_spacing = self.ui.spacing().item_spacing.y;
+        let top = self.build.pinned_top
+            + self
+                .data
+                .peristant
+                .shown_pinned()
+                .take(slot)
+                .map(|id| pinned_row_height(self.ui, self.data.peristant, id) + item_spacing)
+                .sum::<f32>();
+        let row_height = pinned_row_height(self.ui, self.data.peristant, &node.id);
+        let rect = Rect::from_min_size(
+            pos2(self.ui.max_rect().left(), top),
+            vec2(self.ui.available_width(), row_height),
+        );
+
+        node.set_indent(0);
+        node.set_is_open(*open);
+        let mut ui = self.ui.new_child(UiBuilder::new().max_rect(rect));
+        set_row_fg_stroke(
+            &mut ui,
+            self.data.is_selected(&node.id),
+            self.data.has_focus,
+        );
+        if self.data.is_cut(&node.id) {
+            ui.multiply_opacity(0.5);
+        }
+        let (row, closer, icon, label) =
+            node.show_node(&mut ui, self.data, self.settings, self.ctx);
+        let content = icon.map_or(label, |icon| icon.union(label));
+        self.row_pointer_interaction(node, row, content, self.build.pinned_selection_idx);
+        // The drag overlay is only shown once.
+        if self.data.is_dragged(&node.id) && !row_shown {
+            node.show_node_dragged(&mut ui, self.data, self.settings, self.ctx);
+        }
+        self.row_interaction(node, row, closer, content, open);
+    }
+
+    /// Create a click action for each button that clicked the hovered node.
+    fn report_clicks(&mut self, id: NodeIdType) {
+        if !self.settings.report_clicks || !self.settings.interactive {
+            return;
+        }
+        let response = &self.data.interaction_response;
+        for button in [
+            PointerButton::Primary,
+            PointerButton::Secondary,
+            PointerButton::Middle,
+            PointerButton::Extra1,
+            PointerButton::Extra2,
+        ] {
+            if response.clicked_by(button) {
+                self.data.actions.push(Action::NodeClicked {
+                    id,
+                    button,
+                    modifiers: self.ui.input(|i| i.modifiers),
+                    double: response.double_clicked_by(button),
+                });
+            }
+        }
+    }
+
+    /// The part of a row that is covered by its selection background.
+    fn selection_rect(&self, row: Rect, content: Rect) -> Rect {
+        let rect = match self.settings.selection_area {
+            RowArea::FullRow => row,
+            RowArea::LabelOnly => Rect::from_x_y_ranges(
+                content.x_range().expand(self.ui.spacing().item_spacing.x),
+                row.y_range(),
+            ),
+        };
+        rect.shrink2(vec2(self.settings.selection_inset, 0.0))
+            .translate(vec2(0.0, self.build.row_offset))
+    }
+
+    /// The rounding of the selection background.
+    fn selection_rounding(&self) -> egui::Rounding {
+        self.settings
+            .selection_rounding
+            .unwrap_or(self.ui.visuals().widgets.active.rounding)
+    }
+
+    /// The background of a selected row.
+    fn selection_background(&self, row: Rect, content: Rect) -> RectShape {
+        RectShape::new(
+            self.selection_rect(row, content),
+            self.selection_rounding(),
+            if self.data.has_focus {
+                self.ui.visuals().selection.bg_fill
+            } else {
+                self.ui
+                    .visuals()
+                    .widgets
+                    .inactive
+                    .weak_bg_fill
+                    .linear_multiply(0.3)
+            },
+            Stroke::NONE,
+        )
+    }
+
+    /// Count a new child node for the current directory.
+    ///
+    /// Returns `true` if the child is not part of the shown pages of the directory.
+    fn add_child_to_page(&mut self) -> bool {
+        let Some(parent_dir) = self.build.stack.last_mut() else {
+            return false;
+        };
+        parent_dir.child_count += 1;
+        parent_dir
+            .child_page_size
+            .is_some_and(|page_size| parent_dir.child_count > page_size * parent_dir.child_pages)
+    }
+
+    /// Whether the current directory does not show any more children because
+    /// all children of its shown pages were added.
+    ///
+    /// Use this to stop adding the children of a directory with a
+    /// [`child_page_size`](NodeBuilder::child_page_size) early. Set the
+    /// [`child_count`](NodeBuilder::child_count) of the directory so that
+    /// its "show more" row knows how many children there are.
+    /// Children that are not added are not known to the tree view.
+    pub fn is_page_full(&self) -> bool {
+        self.parent_dir_page_full()
+    }
+
+    /// Whether the current directory has already shown all children of its shown pages.
+    fn parent_dir_page_full(&self) -> bool {
+        self.parent_dir().is_some_and(|dir| {
+            dir.child_page_size
+                .is_some_and(|page_size| dir.child_count >= page_size * dir.child_pages)
+        })
+    }
+
+    /// Show a row to reveal more children of a paged directory if
+    /// the directory has more children than are shown.
+    fn show_more_row(&mut self, dir: &mut DirectoryState<NodeIdType>) {
+        let Some(page_size) = dir.child_page_size else {
+            return;
+        };
+        let shown = page_size * dir.child_pages;
+        // Children that are not shown do not have to be added if the child count is known.
+        let child_count = dir.child_count.max(dir.known_child_count.unwrap_or(0));
+        if !dir.is_open || child_count <= shown {
+            return;
+        }
+
+        let text = (self.settings.show_more_text)((child_count - shown).min(page_size));
+        let mut node = NodeBuilder::<(), Ctx>::leaf(()).label(|ui| {
+            ui.add(egui::Label::new(text.clone().weak()).selectable(false));
+        });
+        node.set_indent(dir.indent_level);
+        let (row, _, _, label) = node.show_node(self.ui, self.data, self.settings, self.ctx);
+
+        let row_interaction = self.data.interact(&row);
+        if row_interaction.hovered {
+            self.ui
+                .ctx()
+                .set_cursor_icon(egui::CursorIcon::PointingHand);
+        }
+        if row_interaction.clicked {
+            if let Some(node_state) = self
+                .data
+                .new_node_states
+                .iter_mut()
+                .rev()
+                .find(|node_state| node_state.id == dir.id)
+            {
+                node_state.child_pages += 1;
+            }
+        }
+        dir.child_node_positions.push(self.start_of(label));
+    }
+
+    /// Show a spinner row if the children of the directory are still being loaded.
+    fn loading_row(&mut self, dir: &mut DirectoryState<NodeIdType>) {
+        if !dir.is_open || dir.flattened || !dir.loading {
+            return;
+        }
+        let text = &self.settings.loading_text;
+        let mut node = NodeBuilder::<(), Ctx>::leaf(()).label(|ui| {
+            ui.horizontal(|ui| {
+                ui.add(egui::Spinner::new());
+                ui.add(egui::Label::new(text.clone().weak()).selectable(false));
             });
+        });
+        node.set_indent(dir.indent_level);
+        let (_, _, _, label) = node.show_node(self.ui, self.data, self.settings, self.ctx);
+        dir.child_node_positions.push(self.start_of(label));
+    }
+
+    /// Show a placeholder row if the directory is open but has no children.
+    fn empty_dir_placeholder(&mut self, dir: &mut DirectoryState<NodeIdType>) {
+        let Some(text) = self.settings.empty_dir_placeholder.as_ref() else {
+            return;
+        };
+        if !dir.is_open || dir.flattened || dir.loading || dir.child_count > 0 {
+            return;
+        }
+
+        let mut node = NodeBuilder::<(), Ctx>::leaf(()).label(|ui| {
+            ui.add(egui::Label::new(text.clone().weak()).selectable(false));
+        });
+        node.set_indent(dir.indent_level);
+        let (row, _, _, label) = node.show_node(self.ui, self.data, self.settings, self.ctx);
+        dir.child_node_positions.push(self.start_of(label));
+
+        // Dropping onto the placeholder drops into the directory.
+        let hovered = self.data.drop_pos().is_some_and(|pos| row.contains(pos));
+        let drop_allowed = dir.drop_allowed
+            && !dir.drop_forbidden
+            && !self.settings.drag_reorder_only
+            && dir.allowed_drop_positions.contains(DropPositionMask::INTO)
+            && self.drop_depth_allowed(1);
+        if hovered && drop_allowed && self.data.drag_valid() {
+            self.data.drop = Some((Some(dir.id), DropPosition::Last));
         }
     }
 
-    fn node_internal(&mut self, node: &mut NodeBuilder<NodeIdType>) -> (Rect, Option<Rect>) {
+    /// Drop into the root directory when the pointer is below the last node of the tree.
+    fn root_drop_zone(&mut self) {
+        if !self.settings.root_drop_zone {
+            return;
+        }
+        // Reserve a strip below the last node so that there is always space to drop onto.
+        let top = self.ui.cursor().top();
+        self.ui
+            .allocate_space(vec2(0.0, self.ui.spacing().interact_size.y));
+        if !self.data.drag_valid() {
+            return;
+        }
+        let Some(root_id) = self.build.root_drop_target else {
+            return;
+        };
+        let zone = Rect::from_x_y_ranges(
+            self.ui.max_rect().x_range(),
+            top..=self.ui.max_rect().bottom().max(self.ui.cursor().top()),
+        );
+        let hovered = self.data.drop_pos().is_some_and(|pos| zone.contains(pos));
+        if !hovered {
+            return;
+        }
+        self.data.drop = Some((Some(root_id), DropPosition::Last));
+        let y = zone.top() - self.ui.spacing().item_spacing.y * 0.5;
+        let marker = Rect::from_x_y_ranges(zone.x_range(), y - 1.0..=y + 1.0);
+        self.ui.painter().set(
+            self.data.drop_marker_idx,
+            RectShape::new(
+                marker,
+                0.0,
+                self.settings
+                    .drop_marker_color
+                    .unwrap_or(self.ui.visuals().selection.bg_fill),
+                Stroke::NONE,
+            ),
+        );
+    }
+
+    /// Show the row of a node.
+    ///
+    /// Returns the rect of the row, the closer and the icon and label of the node.
+    fn node_internal(
+        &mut self,
+        node: &mut NodeBuilder<NodeIdType, Ctx>,
+    ) -> (Rect, Option<Rect>, Rect) {
         node.set_indent(self.get_indent_level());
         let (row, closer, icon, label) = self
             .ui
             .scope(|ui| {
-                // Set the fg stroke colors here so that the ui added by the user
-                // has the correct colors when selected or focused.
-                let fg_stroke = if self.data.is_selected(&node.id) && self.data.has_focus {
-                    ui.visuals().selection.stroke
-                } else if self.data.is_selected(&node.id) {
-                    ui.visuals().widgets.inactive.fg_stroke
-                } else {
-                    ui.visuals().widgets.noninteractive.fg_stroke
-                };
-                ui.visuals_mut().widgets.noninteractive.fg_stroke = fg_stroke;
-                ui.visuals_mut().widgets.inactive.fg_stroke = fg_stroke;
+                set_row_fg_stroke(ui, self.data.is_selected(&node.id), self.data.has_focus);
+                if self.data.is_cut(&node.id) {
+                    ui.multiply_opacity(0.5);
+                }
 
-                node.show_node(ui, self.data, self.settings)
+                node.show_node(ui, self.data, self.settings, self.ctx)
             })
             .inner;
 
+        let content = icon.map_or(label, |icon| icon.union(label));
+        self.row_pointer_interaction(node, row, content, self.build.background_idx);
+        if self.data.is_dragged(&node.id) {
+            node.show_node_dragged(self.ui, self.data, self.settings, self.ctx);
+        }
+
+        let left_closer = closer.filter(|_| self.settings.closer_position == CloserPosition::Left);
+        let content_start = self.start_of(left_closer.or(icon).unwrap_or(label));
+        self.do_drop_node(node, &row, content_start.x);
+
+        self.push_child_node_position(content_start);
+
+        (row, closer, content)
+    }
+
+    /// React to the pointer on the row of a node. The row is hovered, selected,
+    /// starts a drag or opens the context menu of the node.
+    ///
+    /// This is the same for the row of the node in the tree and its pinned row.
+    /// The selection background is painted at `background_idx`.
+    fn row_pointer_interaction(
+        &mut self,
+        node: &mut NodeBuilder<NodeIdType, Ctx>,
+        row: Rect,
+        content: Rect,
+        background_idx: ShapeIdx,
+    ) {
         let row_interaction = self.data.interact(&row);
+        if row_interaction.hovered {
+            self.data.hovered = Some(node.id);
+            self.report_clicks(node.id);
+        }
 
         // React to primary clicking
-        if row_interaction.clicked {
+        let secondary_select =
+            row_interaction.secondary_clicked && self.settings.secondary_click_selects;
+        if self.settings.interactive
+            && (row_interaction.clicked || secondary_select)
+            && self.data.peristant.can_select(node.id)
+        {
             self.data.peristant.selected = Some(node.id);
         }
         if self.data.is_selected(&node.id) {
-            self.ui.painter().set(
-                self.background_idx,
-                epaint::RectShape::new(
-                    row,
-                    self.ui.visuals().widgets.active.rounding,
-                    if self.data.has_focus {
-                        self.ui.visuals().selection.bg_fill
-                    } else {
-                        self.ui
-                            .visuals()
-                            .widgets
-                            .inactive
-                            .weak_bg_fill
-                            .linear_multiply(0.3)
-                    },
-                    Stroke::NONE,
-                ),
-            );
+            let background = self.selection_background(row, content);
+            self.tree_painter().set(background_idx, background);
         }
         // React to a dragging
         // An egui drag only starts after the pointer has moved but with that first movement
@@ -259,59 +1411,61 @@ impl<'ui, 'state, NodeIdType: TreeViewId> TreeViewBuilder<'ui, 'state, NodeIdTyp
         let primary_pressed = self
             .ui
             .input(|i| i.pointer.button_pressed(egui::PointerButton::Primary));
-        if row_interaction.hovered && primary_pressed {
+        if row_interaction.hovered && primary_pressed && self.settings.interactive {
             let pointer_pos = self.ui.ctx().pointer_latest_pos().unwrap_or_default();
             self.data.peristant.dragged = Some(DragState {
                 node_id: node.id,
                 drag_row_offset: row.min - pointer_pos,
                 drag_start_pos: pointer_pos,
+                drag_start_time: self.ui.input(|i| i.time),
                 drag_valid: false,
+                swipe: false,
             });
         }
-        if self.data.is_dragged(&node.id) {
-            node.show_node_dragged(self.ui, self.data, self.settings);
-        }
 
         // React to secondary clicks
-        if row_interaction.secondary_clicked {
+        // On a touch screen a long press is a secondary click. The node was pressed
+        // so long that it would be dragged as soon as the finger moves.
+        if row_interaction.secondary_clicked && self.settings.interactive {
             self.data.peristant.secondary_selection = Some(node.id);
+            if !self.data.drag_valid() {
+                self.data.peristant.dragged = None;
+            }
         }
-        if self.data.is_secondary_selected(&node.id) {
-            let context_menu_visible = node.show_context_menu(&self.data.interaction_response);
+        // A pinned node has two rows but only one context menu.
+        if self.data.is_secondary_selected(&node.id) && !self.build.context_menu_shown {
+            self.build.context_menu_shown = true;
+            let context_menu_visible =
+                node.show_context_menu(&self.data.interaction_response, self.ctx);
 
             if !self.data.is_selected(&node.id) && context_menu_visible {
-                self.ui.painter().set(
-                    self.secondary_selection_idx,
+                self.tree_painter().set(
+                    self.build.secondary_selection_idx,
                     epaint::RectShape::new(
-                        row,
-                        self.ui.visuals().widgets.active.rounding,
+                        self.selection_rect(row, content),
+                        self.selection_rounding(),
                         egui::Color32::TRANSPARENT,
                         self.ui.visuals().widgets.inactive.fg_stroke,
                     ),
                 );
             }
         }
-
-        self.do_drop_node(node, &row);
-
-        self.push_child_node_position(closer.or(icon).unwrap_or(label).left_center());
-
-        (row, closer)
     }
 
-    fn do_drop_node(&mut self, node: &NodeBuilder<NodeIdType>, row: &Rect) {
+    fn do_drop_node(
+        &mut self,
+        node: &NodeBuilder<NodeIdType, Ctx>,
+        row: &Rect,
+        content_start: f32,
+    ) {
         let Some(drop_quarter) = self
             .data
-            .interaction_response
-            .hover_pos()
+            .drop_pos()
             .and_then(|pos| DropQuarter::new(row.y_range(), pos.y))
         else {
             return;
         };
 
-        if self.data.peristant.dragged.is_none() {
-            return;
-        }
         if !self.data.drag_valid() {
             return;
         }
@@ -325,129 +1479,289 @@ impl<'ui, 'state, NodeIdType: TreeViewId> TreeViewBuilder<'ui, 'state, NodeIdTyp
         }
 
         let drop_position = self.get_drop_position_node(node, &drop_quarter);
-        let shape = self.drop_marker_shape(row, drop_position.as_ref());
+        let mut shape = self.drop_marker_shape(row, content_start, drop_position.as_ref());
+        // Dropping into an open directory shows a line below the directory row.
+        // Outline the directory so this can be told apart from dropping after it.
+        if let Some((_, DropPosition::First)) = drop_position {
+            shape = Shape::Vec(vec![shape, self.drop_target_dir_outline(row)]);
+        }
 
         // It is allowed to drop itself `After´ or `Before` itself.
         // This however doesn't make sense and makes executing the command more
         // difficult for the caller.
         // Instead we display the markers only.
         if self.data.is_dragged(&node.id) {
-            self.ui.painter().set(self.data.drop_marker_idx, shape);
+            self.tree_painter().set(self.data.drop_marker_idx, shape);
             return;
         }
 
         self.data.drop = drop_position;
-        self.ui.painter().set(self.data.drop_marker_idx, shape);
+        self.tree_painter().set(self.data.drop_marker_idx, shape);
     }
 
     fn get_drop_position_node(
         &self,
-        node_config: &NodeBuilder<NodeIdType>,
+        node_config: &NodeBuilder<NodeIdType, Ctx>,
         drop_quater: &DropQuarter,
-    ) -> Option<(NodeIdType, DropPosition<NodeIdType>)> {
+    ) -> Option<(Option<NodeIdType>, DropPosition<NodeIdType>)> {
         let NodeBuilder {
             id,
+            is_dir,
             is_open,
             drop_allowed,
+            allowed_drop_positions,
             ..
         } = node_config;
 
-        match drop_quater {
-            DropQuarter::Top => {
-                if let Some(parent_dir) = self.parent_dir() {
-                    return Some((parent_dir.id, DropPosition::Before(*id)));
-                }
-                if *drop_allowed {
-                    return Some((*id, DropPosition::Last));
-                }
-                None
-            }
-            DropQuarter::MiddleTop => {
-                if *drop_allowed {
-                    return Some((*id, DropPosition::Last));
-                }
-                if let Some(parent_dir) = self.parent_dir() {
-                    return Some((parent_dir.id, DropPosition::Before(*id)));
-                }
-                None
-            }
-            DropQuarter::MiddleBottom => {
-                if *drop_allowed {
-                    return Some((*id, DropPosition::Last));
-                }
-                if let Some(parent_dir) = self.parent_dir() {
-                    return Some((parent_dir.id, DropPosition::After(*id)));
-                }
-                None
-            }
-            DropQuarter::Bottom => {
-                if *drop_allowed && *is_open {
-                    return Some((*id, DropPosition::First));
-                }
-                if let Some(parent_dir) = self.parent_dir() {
-                    return Some((parent_dir.id, DropPosition::After(*id)));
-                }
-                if *drop_allowed {
-                    return Some((*id, DropPosition::Last));
+        let reorder_only = self.settings.drag_reorder_only;
+        let dragged_parent_id = self.data.dragged_parent_id();
+        let drop_into_allowed = *drop_allowed
+            && !reorder_only
+            && allowed_drop_positions.contains(DropPositionMask::INTO);
+
+        // Directories receive the dropped node as their last child.
+        // Leaves receive the dropped node onto themself.
+        let drop_onto = drop_into_allowed
+            .then(|| {
+                if *is_dir {
+                    (Some(*id), DropPosition::Last)
+                } else {
+                    (self.parent_id(), DropPosition::On(*id))
                 }
-                None
-            }
+            })
+            .filter(|_| self.drop_depth_allowed(if *is_dir { 1 } else { 0 }));
+        let drop_first = (drop_into_allowed && *is_dir && *is_open && self.drop_depth_allowed(1))
+            .then_some((Some(*id), DropPosition::First));
+        // Nodes at the top level of a tree without a root directory
+        // can be dropped before and after each other.
+        // With `drag_reorder_only` the node stays with its parent, which
+        // is `None` for nodes at the top level.
+        let same_parent = dragged_parent_id == self.parent_id();
+        let parent_allows = |mask| {
+            (!reorder_only || same_parent)
+                && self
+                    .parent_dir()
+                    .map_or(true, |dir| dir.allowed_drop_positions.contains(mask))
+        };
+        let drop_before = (parent_allows(DropPositionMask::BEFORE) && self.drop_depth_allowed(0))
+            .then_some((self.parent_id(), DropPosition::Before(*id)));
+        let drop_after = (parent_allows(DropPositionMask::AFTER) && self.drop_depth_allowed(0))
+            .then_some((self.parent_id(), DropPosition::After(*id)));
+
+        // Each quarter has a preferred drop position. If that position is not
+        // allowed we fall back to the next best position.
+        match drop_quater {
+            DropQuarter::Top => drop_before.or(drop_onto),
+            DropQuarter::MiddleTop => drop_onto.or(drop_before),
+            DropQuarter::MiddleBottom => drop_onto.or(drop_after),
+            DropQuarter::Bottom => drop_first.or(drop_after).or(drop_onto),
         }
     }
 
+    /// Whether the dragged node is allowed to be dropped at a depth of
+    /// `extra_depth` levels below the current node.
+    ///
+    /// The depth of the dragged nodes subtree is taken into account.
+    fn drop_depth_allowed(&self, extra_depth: usize) -> bool {
+        let Some(max_drop_depth) = self.settings.max_drop_depth else {
+            return true;
+        };
+        self.build.stack.len() + extra_depth + self.data.dragged_subtree_height <= max_drop_depth
+    }
+
     fn drop_marker_shape(
         &self,
         interaction: &Rect,
-        drop_position: Option<&(NodeIdType, DropPosition<NodeIdType>)>,
+        content_start: f32,
+        drop_position: Option<&(Option<NodeIdType>, DropPosition<NodeIdType>)>,
     ) -> Shape {
-        pub const DROP_LINE_HEIGHT: f32 = 3.0;
+        let color = self
+            .settings
+            .drop_marker_color
+            .unwrap_or(self.ui.visuals().selection.bg_fill)
+            .linear_multiply(0.6);
+        let thickness = self.settings.drop_marker_thickness;
 
-        let drop_marker = match drop_position {
-            Some((_, DropPosition::Before(_))) => {
-                Rangef::point(interaction.min.y).expand(DROP_LINE_HEIGHT * 0.5)
-            }
-            Some((_, DropPosition::First)) | Some((_, DropPosition::After(_))) => {
-                Rangef::point(interaction.max.y).expand(DROP_LINE_HEIGHT * 0.5)
+        let dir = self.text_dir();
+        let (line_y, line_start) = match drop_position {
+            Some((_, DropPosition::Before(_))) => (interaction.min.y, content_start),
+            Some((_, DropPosition::After(_))) => (interaction.max.y, content_start),
+            Some((_, DropPosition::First)) => (
+                interaction.max.y,
+                content_start
+                    + dir
+                        * self
+                            .settings
+                            .override_indent
+                            .unwrap_or(self.ui.spacing().indent),
+            ),
+            Some((_, DropPosition::Last)) | Some((_, DropPosition::On(_))) => {
+                return epaint::RectShape::new(
+                    *interaction,
+                    self.ui.visuals().widgets.active.rounding,
+                    color,
+                    Stroke::NONE,
+                )
+                .into();
             }
-            Some((_, DropPosition::Last)) => interaction.y_range(),
             None => return Shape::Noop,
         };
 
-        epaint::RectShape::new(
-            Rect::from_x_y_ranges(interaction.x_range(), drop_marker),
+        let line_y_range = Rangef::point(line_y).expand(thickness * 0.5);
+        match self.settings.drop_marker_style {
+            DropMarkerStyle::Line => epaint::RectShape::new(
+                Rect::from_x_y_ranges(interaction.x_range(), line_y_range),
+                self.ui.visuals().widgets.active.rounding,
+                color,
+                Stroke::NONE,
+            )
+            .into(),
+            // The highlight of the target directory is drawn when the directory is closed.
+            DropMarkerStyle::RowHighlight => Shape::Noop,
+            DropMarkerStyle::Caret => {
+                let caret_size = thickness + 4.0;
+                let mut caret = vec![
+                    pos2(line_start, line_y - caret_size),
+                    pos2(line_start + dir * caret_size, line_y),
+                    pos2(line_start, line_y + caret_size),
+                ];
+                // Keep the points in clockwise order.
+                if self.settings.right_to_left {
+                    caret.reverse();
+                }
+                let line_x_range = if self.settings.right_to_left {
+                    Rangef::new(interaction.min.x, line_start)
+                } else {
+                    Rangef::new(line_start, interaction.max.x)
+                };
+                Shape::Vec(vec![
+                    Shape::convex_polygon(caret, color, Stroke::NONE),
+                    epaint::RectShape::new(
+                        Rect::from_x_y_ranges(line_x_range, line_y_range),
+                        self.ui.visuals().widgets.active.rounding,
+                        color,
+                        Stroke::NONE,
+                    )
+                    .into(),
+                ])
+            }
+        }
+    }
+
+    /// Outline of the row of a directory that is the target of a drop.
+    fn drop_target_dir_outline(&self, row: &Rect) -> Shape {
+        epaint::RectShape::stroke(
+            *row,
             self.ui.visuals().widgets.active.rounding,
-            self.ui
-                .style()
-                .visuals
-                .selection
-                .bg_fill
-                .linear_multiply(0.6),
-            Stroke::NONE,
+            Stroke::new(
+                1.0,
+                self.settings
+                    .drop_marker_color
+                    .unwrap_or(self.ui.visuals().selection.bg_fill),
+            ),
         )
         .into()
     }
 
     fn parent_dir(&self) -> Option<&DirectoryState<NodeIdType>> {
-        if self.stack.is_empty() {
+        if self.build.stack.is_empty() {
             None
         } else {
-            self.stack.last()
+            self.build.stack.last()
         }
     }
     fn parent_dir_is_open(&self) -> bool {
         self.parent_dir().map_or(true, |dir| dir.is_open)
     }
 
+    fn parent_dir_closing(&self) -> bool {
+        self.parent_dir().is_some_and(|dir| dir.closing)
+    }
+
     fn parent_dir_drop_forbidden(&self) -> bool {
         self.parent_dir().is_some_and(|dir| dir.drop_forbidden)
     }
 
     fn push_child_node_position(&mut self, pos: Pos2) {
-        if let Some(parent_dir) = self.stack.last_mut() {
+        if let Some(parent_dir) = self.build.stack.last_mut() {
             parent_dir.child_node_positions.push(pos);
         }
     }
     fn get_indent_level(&self) -> usize {
-        self.stack.last().map(|d| d.indent_level).unwrap_or(0)
+        self.build.stack.last().map(|d| d.indent_level).unwrap_or(0)
     }
 }
+
+/// A plain text label of a node whose galley is cached by the tree view.
+struct CachedLabel<'a> {
+    text: &'a str,
+    /// The galley of the label and the key it was laid out for.
+    galley: Option<(Id, Arc<Galley>)>,
+}
+impl CachedLabel<'_> {
+    /// Show the label and lay it out again if the cached galley does not fit the ui.
+    fn show(&mut self, ui: &mut Ui) {
+        let font_id = TextStyle::Body.resolve(ui.style());
+        // egui recreates the font atlas when it is full or the scale changes.
+        // Galleys laid out for an old atlas can not be used anymore.
+        let atlas = ui.fonts(|fonts| Arc::as_ptr(&fonts.texture_atlas()) as usize);
+        let wrapping = match ui.wrap_mode() {
+            egui::TextWrapMode::Extend => TextWrapping::no_max_width(),
+            egui::TextWrapMode::Wrap => TextWrapping::wrap_at_width(ui.available_width()),
+            // The label shows the complete text on hover if it was truncated.
+            egui::TextWrapMode::Truncate => TextWrapping::truncate_at_width(ui.available_width()),
+        };
+        let key = Id::new((
+            self.text,
+            &font_id,
+            atlas,
+            wrapping.max_width.to_bits(),
+            wrapping.max_rows,
+            wrapping.break_anywhere,
+        ));
+        let galley = match &self.galley {
+            Some((cached_key, galley)) if *cached_key == key => Arc::clone(galley),
+            _ => {
+                let mut job = LayoutJob::single_section(
+                    self.text.to_owned(),
+                    TextFormat::simple(font_id, Color32::PLACEHOLDER),
+                );
+                job.wrap = wrapping;
+                let galley = ui.fonts(|fonts| fonts.layout_job(job));
+                self.galley = Some((key, Arc::clone(&galley)));
+                galley
+            }
+        };
+        ui.add(egui::Label::new(galley).selectable(false));
+    }
+}
+
+/// Height of the row of a pinned node.
+///
+/// Pinned rows are as tall as the content of the node was the last time
+/// it was shown in the tree.
+fn pinned_row_height<NodeIdType: TreeViewId>(
+    ui: &Ui,
+    state: &TreeViewState<NodeIdType>,
+    id: &NodeIdType,
+) -> f32 {
+    state
+        .row_height_of(*id)
+        .map(|row_height| row_height - ui.spacing().item_spacing.y)
+        .filter(|height| *height > 0.0)
+        .unwrap_or(ui.spacing().interact_size.y)
+}
+
+/// Set the fg stroke colors so that the ui added by the user
+/// has the correct colors when selected or focused.
+fn set_row_fg_stroke(ui: &mut Ui, selected: bool, has_focus: bool) {
+    let fg_stroke = if selected && has_focus {
+        ui.visuals().selection.stroke
+    } else if selected {
+        ui.visuals().widgets.inactive.fg_stroke
+    } else {
+        ui.visuals().widgets.noninteractive.fg_stroke
+    };
+    ui.visuals_mut().widgets.noninteractive.fg_stroke = fg_stroke;
+    ui.visuals_mut().widgets.inactive.fg_stroke = fg_stroke;
+}
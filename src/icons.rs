@@ -0,0 +1,92 @@
+//! Default icons for nodes in the tree view.
+use egui::{epaint::PathStroke, pos2, vec2, Color32, Rect, Shape, Stroke, Ui};
+
+/// A default icon that can be shown for a node with
+/// [`NodeBuilder::default_icon`](crate::node::NodeBuilder::default_icon).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconKind {
+    /// A folder. For directories the folder is shown open while the directory is open.
+    Folder,
+    /// A folder that is always shown open.
+    FolderOpen,
+    /// A file.
+    File,
+}
+
+/// Paint the icon into the rect of the ui.
+pub(crate) fn paint_icon(ui: &mut Ui, kind: IconKind, open: bool) {
+    let color = ui.visuals().widgets.noninteractive.fg_stroke.color;
+    let (small_rect, _) = ui.spacing().icon_rectangles(ui.max_rect());
+    let rect = Rect::from_center_size(small_rect.center(), small_rect.size() * 1.2);
+    match kind {
+        IconKind::Folder if open => paint_folder_open(ui, rect, color),
+        IconKind::Folder => paint_folder(ui, rect, color),
+        IconKind::FolderOpen => paint_folder_open(ui, rect, color),
+        IconKind::File => paint_file(ui, rect, color),
+    }
+}
+
+fn paint_folder(ui: &mut Ui, rect: Rect, color: Color32) {
+    let stroke = Stroke::new(1.0, color);
+    let tab = rect.width() * 0.4;
+    let top = rect.top() + rect.height() * 0.15;
+    let body_top = top + rect.height() * 0.15;
+    let points = vec![
+        pos2(rect.left(), top),
+        pos2(rect.left() + tab, top),
+        pos2(rect.left() + tab + 1.5, body_top),
+        pos2(rect.right(), body_top),
+        pos2(rect.right(), rect.bottom()),
+        pos2(rect.left(), rect.bottom()),
+    ];
+    ui.painter()
+        .add(Shape::closed_line(points, PathStroke::from(stroke)));
+}
+
+fn paint_folder_open(ui: &mut Ui, rect: Rect, color: Color32) {
+    let stroke = Stroke::new(1.0, color);
+    let tab = rect.width() * 0.4;
+    let top = rect.top() + rect.height() * 0.15;
+    let body_top = top + rect.height() * 0.15;
+    let front_top = rect.top() + rect.height() * 0.5;
+    let back = vec![
+        pos2(rect.left(), rect.bottom()),
+        pos2(rect.left(), top),
+        pos2(rect.left() + tab, top),
+        pos2(rect.left() + tab + 1.5, body_top),
+        pos2(rect.right() - rect.width() * 0.15, body_top),
+        pos2(rect.right() - rect.width() * 0.15, front_top),
+    ];
+    ui.painter()
+        .add(Shape::line(back, PathStroke::from(stroke)));
+    let front = vec![
+        pos2(rect.left(), rect.bottom()),
+        pos2(rect.left() + rect.width() * 0.2, front_top),
+        pos2(rect.right() + rect.width() * 0.1, front_top),
+        pos2(rect.right() - rect.width() * 0.1, rect.bottom()),
+    ];
+    ui.painter()
+        .add(Shape::closed_line(front, PathStroke::from(stroke)));
+}
+
+fn paint_file(ui: &mut Ui, rect: Rect, color: Color32) {
+    let stroke = Stroke::new(1.0, color);
+    let rect = Rect::from_center_size(rect.center(), vec2(rect.width() * 0.75, rect.height()));
+    let fold = rect.width() * 0.35;
+    let points = vec![
+        pos2(rect.left(), rect.top()),
+        pos2(rect.right() - fold, rect.top()),
+        pos2(rect.right(), rect.top() + fold),
+        pos2(rect.right(), rect.bottom()),
+        pos2(rect.left(), rect.bottom()),
+    ];
+    ui.painter()
+        .add(Shape::closed_line(points, PathStroke::from(stroke)));
+    let corner = vec![
+        pos2(rect.right() - fold, rect.top()),
+        pos2(rect.right() - fold, rect.top() + fold),
+        pos2(rect.right(), rect.top() + fold),
+    ];
+    ui.painter()
+        .add(Shape::line(corner, PathStroke::from(stroke)));
+}
@@ -1,32 +1,44 @@
 pub mod builder;
+#[cfg(feature = "icons")]
+pub mod icons;
 pub mod node;
 
-use std::hash::Hash;
+use std::{any::Any, cell::Cell, collections::HashMap, hash::Hash, sync::Arc, time::Duration};
 
 use egui::{
-    self, layers::ShapeIdx, vec2, Event, EventFilter, Id, Key, Layout, NumExt, Pos2, Rect,
-    Response, Sense, Shape, Ui, Vec2,
+    self, ahash, layers::ShapeIdx, remap, style::ScrollAnimation, vec2, Color32, Event,
+    EventFilter, Galley, Id, Key, Layout, NumExt, Pos2, Rangef, Rect, Response, Rounding, Sense,
+    Shape, Ui, Vec2, WidgetText,
 };
 
+use builder::BuildState;
 pub use builder::TreeViewBuilder;
 
+/// Ids of the nodes in a tree view.
+///
+/// Ids are copied whenever the tree is shown, so they have to be `Copy`. Ids that
+/// are not `Copy`, like `Rc<str>` or `String`, can be interned by the application
+/// so that the tree is shown with a `Copy` handle to them, e.g. an index.
 pub trait TreeViewId: Clone + Copy + PartialEq + Eq + Hash {}
 impl<T> TreeViewId for T where T: Clone + Copy + PartialEq + Eq + Hash {}
 
-#[cfg(feature = "persistence")]
-pub trait NodeId:
-    TreeViewId + Send + Sync + 'static + serde::de::DeserializeOwned + serde::Serialize
-{
-}
-#[cfg(feature = "persistence")]
-impl<T> NodeId for T where
-    T: TreeViewId + Send + Sync + 'static + serde::de::DeserializeOwned + serde::Serialize
-{
+/// Converts node ids to strings and back.
+///
+/// Used to store the state of a tree view with [`TreeViewState::save_with`]
+/// or [`TreeView::show_persisted`] without the node ids being serializable,
+/// e.g. by mapping them to stable paths.
+pub trait StatePersistence<NodeIdType> {
+    /// Get the key that is stored for a node id.
+    fn id_to_key(&self, id: &NodeIdType) -> String;
+    /// Get the node id for a stored key. `None` if the node does not exist anymore.
+    fn key_to_id(&self, key: &str) -> Option<NodeIdType>;
 }
 
-#[cfg(not(feature = "persistence"))]
+/// Node ids that can be used with [`TreeView::show`], which keeps the state in egui memory.
+///
+/// The state is kept in egui's temporary memory. Use [`TreeView::show_persisted`]
+/// to also store it with egui's persisted data.
 pub trait NodeId: TreeViewId + Send + Sync + 'static {}
-#[cfg(not(feature = "persistence"))]
 impl<T> NodeId for T where T: TreeViewId + Send + Sync + 'static {}
 
 /// Represents the state of the tree view.
@@ -46,6 +58,60 @@ pub struct TreeViewState<NodeIdType> {
     size: Vec2,
     /// Open states of the dirs in this tree.
     node_states: Vec<NodeState<NodeIdType>>,
+    /// Nodes that are additionally shown at the top of the tree.
+    pinned: Vec<NodeIdType>,
+    /// Index of each node in the node states.
+    #[cfg_attr(
+        feature = "persistence",
+        serde(skip, default = "ahash::HashMap::default")
+    )]
+    node_index: ahash::HashMap<NodeIdType, usize>,
+    /// The drop target of the current drag.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "Option::default"))]
+    drop_target: Option<(Option<NodeIdType>, DropPosition<NodeIdType>)>,
+    /// The selection that was last reported with [`Action::SetSelected`].
+    #[cfg_attr(feature = "persistence", serde(skip, default = "Option::default"))]
+    reported_selection: Option<NodeIdType>,
+    /// Node that was cut or copied.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "Option::default"))]
+    clipboard: Option<(NodeIdType, ClipboardOperation)>,
+    /// Node that should be scrolled to the next time it is shown.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "Option::default"))]
+    scroll_to_node: Option<NodeIdType>,
+    /// Nodes that should be activated the next time the tree is shown.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "Vec::default"))]
+    pending_activations: Vec<NodeIdType>,
+    /// Wether the tree view should take keyboard focus the next time it is shown.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "bool::default"))]
+    focus_requested: bool,
+    /// Wether the tree view had keyboard focus when it was last shown.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "bool::default"))]
+    has_focus: bool,
+    /// Wether an [`Action::Move`] was created the last time the tree was shown.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "bool::default"))]
+    moved: bool,
+    /// Rows that slide to their new position after a move, with the offset
+    /// they started at and the time the slide started.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "HashMap::default"))]
+    relocations: HashMap<NodeIdType, (f32, f64)>,
+    /// Vertical velocity of the last swipe that keeps scrolling the tree after it was released.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "f32::default"))]
+    swipe_velocity: f32,
+    /// Wether this state was restored from storage and the [`PersistMode`]
+    /// has not been applied to it yet. A new state is never restored.
+    #[cfg(feature = "persistence")]
+    #[serde(skip, default = "restored_default")]
+    restored: bool,
+    /// Cached galleys of plain text labels with the key they were laid out for.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "HashMap::default"))]
+    label_galleys: HashMap<NodeIdType, (Id, Arc<Galley>)>,
+    /// Open states of directories from a snapshot that were not part of
+    /// the tree yet. They are applied when the directory is added to the tree.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "HashMap::default"))]
+    pending_openness: HashMap<NodeIdType, bool>,
+    /// Decides which nodes the user can select.
+    #[cfg_attr(feature = "persistence", serde(skip, default = "Option::default"))]
+    selection_policy: Option<SelectionPolicy<NodeIdType>>,
 }
 impl<NodeIdType> Default for TreeViewState<NodeIdType> {
     fn default() -> Self {
@@ -55,6 +121,23 @@ impl<NodeIdType> Default for TreeViewState<NodeIdType> {
             secondary_selection: Default::default(),
             size: Vec2::ZERO,
             node_states: Vec::new(),
+            pinned: Vec::new(),
+            node_index: Default::default(),
+            drop_target: None,
+            reported_selection: None,
+            clipboard: None,
+            scroll_to_node: None,
+            pending_activations: Vec::new(),
+            focus_requested: false,
+            has_focus: false,
+            swipe_velocity: 0.0,
+            moved: false,
+            relocations: HashMap::new(),
+            #[cfg(feature = "persistence")]
+            restored: false,
+            label_galleys: HashMap::new(),
+            pending_openness: HashMap::new(),
+            selection_policy: None,
         }
     }
 }
@@ -70,6 +153,297 @@ impl<NodeIdType: TreeViewId> TreeViewState<NodeIdType> {
         self.selected = selected;
     }
 
+    /// Set which nodes the user can select.
+    ///
+    /// The policy is asked before a node is selected by clicking it or by navigating
+    /// to it with the keyboard. Keyboard navigation skips nodes that cannot be selected.
+    /// Selecting a node through the state, e.g. with [`Self::set_selected`], ignores the policy.
+    pub fn set_selection_policy(
+        &mut self,
+        policy: impl Fn(NodeIdType) -> bool + Send + Sync + 'static,
+    ) {
+        self.selection_policy = Some(Arc::new(policy));
+    }
+
+    /// Remove the selection policy so that all nodes can be selected again.
+    pub fn clear_selection_policy(&mut self) {
+        self.selection_policy = None;
+    }
+
+    /// Whether the user can select a node.
+    pub(crate) fn can_select(&self, id: NodeIdType) -> bool {
+        self.selection_policy
+            .as_ref()
+            .map_or(true, |policy| policy(id))
+    }
+
+    /// Return the target and position the dragged node would be dropped to
+    /// if the drag was released now.
+    ///
+    /// This is the same information that is part of the [`Action::Drag`] and
+    /// [`Action::Move`] actions. `None` if no node is dragged or the
+    /// pointer is not over a valid drop position.
+    /// The target is `None` for the top level of a tree without a root directory.
+    pub fn drop_target(&self) -> Option<(Option<NodeIdType>, DropPosition<NodeIdType>)> {
+        self.drop_target
+    }
+
+    /// Activate a node.
+    ///
+    /// The tree view creates an [`Action::Activate`] for this node the next
+    /// time it is shown.
+    pub fn activate(&mut self, id: NodeIdType) {
+        self.pending_activations.push(id);
+    }
+
+    /// Pin a node.
+    ///
+    /// A pinned node is additionally shown in a block at the top of the tree.
+    /// Pinned nodes are only shown if they are added to the tree, even if their
+    /// parent directory is closed. A newly pinned node is shown from the frame
+    /// after it was first added to the tree.
+    pub fn pin(&mut self, id: NodeIdType) {
+        if !self.pinned.contains(&id) {
+            self.pinned.push(id);
+        }
+    }
+
+    /// Unpin a node.
+    pub fn unpin(&mut self, id: NodeIdType) {
+        self.pinned.retain(|pinned| pinned != &id);
+    }
+
+    /// Whether or not a node is pinned.
+    pub fn is_pinned(&self, id: NodeIdType) -> bool {
+        self.pinned.contains(&id)
+    }
+
+    /// Get all pinned nodes in the order they are shown.
+    pub fn pinned(&self) -> &[NodeIdType] {
+        &self.pinned
+    }
+
+    /// The pinned nodes that were part of the tree the last time it was shown.
+    ///
+    /// Only these get a row in the block of pinned nodes.
+    fn shown_pinned(&self) -> impl Iterator<Item = &NodeIdType> {
+        self.pinned
+            .iter()
+            .filter(|id| self.node_state_of(id).is_some())
+    }
+
+    /// Get the node that was cut or copied and how.
+    pub fn clipboard(&self) -> Option<(NodeIdType, ClipboardOperation)> {
+        self.clipboard
+    }
+
+    /// Clear the node that was cut or copied.
+    pub fn clear_clipboard(&mut self) {
+        self.clipboard = None;
+    }
+
+    /// Whether a node can be pasted at a position in a directory.
+    ///
+    /// A node cannot be pasted into itself or one of its descendants and the
+    /// directory has to accept nodes at that position. A `target` of `None`
+    /// is the top level of a tree without a root directory.
+    pub fn paste_allowed(
+        &self,
+        source: NodeIdType,
+        target: Option<NodeIdType>,
+        position: &DropPosition<NodeIdType>,
+    ) -> bool {
+        let Some(target) = target else {
+            return true;
+        };
+        if target == source || self.ancestors(target).any(|ancestor| ancestor == source) {
+            return false;
+        }
+        self.node_state_of(&target).is_some_and(|node_state| {
+            node_state.drop_allowed
+                && node_state
+                    .allowed_drop_positions
+                    .contains(DropPositionMask::of(position))
+        })
+    }
+
+    /// Move the keyboard focus to the tree view the next time it is shown.
+    ///
+    /// Has no effect if the tree view is not [`focusable`](TreeView::focusable).
+    pub fn request_focus(&mut self) {
+        self.focus_requested = true;
+    }
+
+    /// Whether the tree view had the keyboard focus when it was last shown.
+    pub fn has_focus(&self) -> bool {
+        self.has_focus
+    }
+
+    /// Show the path from the root of the tree to the selected node as a row of buttons.
+    ///
+    /// `label_of` provides the label of each node on the path. Clicking a node
+    /// [reveals](Self::reveal) it.
+    ///
+    /// Returns the node that was clicked.
+    pub fn breadcrumb(
+        &mut self,
+        ui: &mut Ui,
+        mut label_of: impl FnMut(NodeIdType) -> WidgetText,
+    ) -> Option<NodeIdType> {
+        let path = self.selected.map(|id| self.path_of(id)).unwrap_or_default();
+        let clicked = ui
+            .horizontal(|ui| {
+                let mut clicked = None;
+                for (i, id) in path.into_iter().enumerate() {
+                    if i > 0 {
+                        ui.label(egui::RichText::new("›").weak());
+                    }
+                    if ui
+                        .add(egui::Button::new(label_of(id)).frame(false))
+                        .clicked()
+                    {
+                        clicked = Some(id);
+                    }
+                }
+                clicked
+            })
+            .inner;
+        if let Some(id) = clicked {
+            self.reveal(id);
+        }
+        clicked
+    }
+
+    /// Replace the ids of nodes with new ids.
+    ///
+    /// Use this when the nodes of the tree get new ids, e.g. after reloading them,
+    /// to keep their state like which directories are open and which node is selected.
+    /// Ids that are not part of the map are kept.
+    pub fn remap_ids(&mut self, map: &HashMap<NodeIdType, NodeIdType>) {
+        let remap = |id: &mut NodeIdType| {
+            if let Some(new_id) = map.get(id) {
+                *id = *new_id;
+            }
+        };
+        for node_state in self.node_states.iter_mut() {
+            remap(&mut node_state.id);
+            if let Some(parent_id) = node_state.parent_id.as_mut() {
+                remap(parent_id);
+            }
+        }
+        for id in self
+            .selected
+            .iter_mut()
+            .chain(self.reported_selection.iter_mut())
+            .chain(self.secondary_selection.iter_mut())
+            .chain(self.scroll_to_node.iter_mut())
+            .chain(self.pinned.iter_mut())
+            .chain(self.pending_activations.iter_mut())
+        {
+            remap(id);
+        }
+        if let Some(drag_state) = self.dragged.as_mut() {
+            remap(&mut drag_state.node_id);
+        }
+        if let Some((id, _)) = self.clipboard.as_mut() {
+            remap(id);
+        }
+        if let Some((target, position)) = self.drop_target.as_mut() {
+            if let Some(target) = target {
+                remap(target);
+            }
+            match position {
+                DropPosition::First | DropPosition::Last => (),
+                DropPosition::After(id) | DropPosition::Before(id) | DropPosition::On(id) => {
+                    remap(id)
+                }
+            }
+        }
+        self.pending_openness = std::mem::take(&mut self.pending_openness)
+            .into_iter()
+            .map(|(mut id, open)| {
+                remap(&mut id);
+                (id, open)
+            })
+            .collect();
+        // Cached labels are created again when they are needed.
+        self.label_galleys.clear();
+        let node_states = std::mem::take(&mut self.node_states);
+        self.set_node_states(node_states);
+    }
+
+    /// Replace the id of a node with a new id.
+    ///
+    /// See [`Self::remap_ids`].
+    pub fn rename_id(&mut self, old_id: NodeIdType, new_id: NodeIdType) {
+        self.remap_ids(&HashMap::from([(old_id, new_id)]));
+    }
+
+    /// Select a node, open all its parent directories and scroll to it
+    /// the next time the tree is shown.
+    pub fn reveal(&mut self, id: NodeIdType) {
+        self.expand_parents_of(id, false);
+        self.selected = Some(id);
+        self.scroll_to_node = Some(id);
+    }
+
+    /// Find the next node after the selected node that matches the predicate.
+    ///
+    /// Nodes are searched in the order they were added to the tree the last time
+    /// it was shown. The search wraps around at the end of the tree and starts at
+    /// the beginning if no node is selected.
+    pub fn find_next(&self, mut matches: impl FnMut(&NodeIdType) -> bool) -> Option<NodeIdType> {
+        let start = self
+            .selected
+            .and_then(|id| self.node_index_of(&id))
+            .map_or(0, |index| index + 1);
+        (start..self.node_states.len())
+            .chain(0..start)
+            .map(|index| self.node_states[index].id)
+            .find(|id| matches(id))
+    }
+
+    /// Find the previous node before the selected node that matches the predicate.
+    ///
+    /// See [`TreeViewState::find_next`].
+    pub fn find_prev(&self, mut matches: impl FnMut(&NodeIdType) -> bool) -> Option<NodeIdType> {
+        let start = self
+            .selected
+            .and_then(|id| self.node_index_of(&id))
+            .unwrap_or(self.node_states.len());
+        (0..start)
+            .rev()
+            .chain((start..self.node_states.len()).rev())
+            .map(|index| self.node_states[index].id)
+            .find(|id| matches(id))
+    }
+
+    /// [Reveal](TreeViewState::reveal) the next node after the selected node that
+    /// matches the predicate, e.g. for a "find next" shortcut.
+    ///
+    /// Returns the revealed node. See [`TreeViewState::find_next`].
+    pub fn select_next_match(
+        &mut self,
+        matches: impl FnMut(&NodeIdType) -> bool,
+    ) -> Option<NodeIdType> {
+        let id = self.find_next(matches)?;
+        self.reveal(id);
+        Some(id)
+    }
+
+    /// [Reveal](TreeViewState::reveal) the previous node before the selected node
+    /// that matches the predicate.
+    ///
+    /// Returns the revealed node. See [`TreeViewState::find_prev`].
+    pub fn select_prev_match(
+        &mut self,
+        matches: impl FnMut(&NodeIdType) -> bool,
+    ) -> Option<NodeIdType> {
+        let id = self.find_prev(matches)?;
+        self.reveal(id);
+        Some(id)
+    }
+
     /// Expand all parent nodes of the node with the given id.
     pub fn expand_parents_of(&mut self, id: NodeIdType, include_self: bool) {
         let mut current_node = if include_self {
@@ -89,22 +463,328 @@ impl<NodeIdType: TreeViewId> TreeViewState<NodeIdType> {
         }
     }
 
+    /// Get the height of the row of a node the last time it was shown.
+    ///
+    /// Rows can have different heights if their labels wrap or contain
+    /// larger widgets. `None` if the node has never been shown.
+    pub fn row_height_of(&self, id: NodeIdType) -> Option<f32> {
+        self.node_state_of(&id)
+            .map(|node_state| node_state.row_height)
+            .filter(|row_height| *row_height > 0.0)
+    }
+
     /// Get the parent id of a node.
     pub fn parent_id_of(&self, id: NodeIdType) -> Option<NodeIdType> {
         self.node_state_of(&id)
             .and_then(|node_state| node_state.parent_id)
     }
 
+    /// Whether or not a node is open.
+    ///
+    /// `None` if the node was not part of the tree the last time it was shown.
+    pub fn is_open(&self, id: NodeIdType) -> Option<bool> {
+        self.node_state_of(&id).map(|node_state| node_state.open)
+    }
+
+    /// The kind of a node set with [`NodeBuilder::kind`](node::NodeBuilder::kind).
+    ///
+    /// `None` if the node was not part of the tree the last time it was shown.
+    pub fn kind_of(&self, id: NodeIdType) -> Option<u32> {
+        self.node_state_of(&id).map(|node_state| node_state.kind)
+    }
+
+    /// Get the ids of all nodes in the order they were added to the tree
+    /// the last time it was shown.
+    pub fn iter_nodes(&self) -> impl Iterator<Item = NodeIdType> + '_ {
+        self.node_states.iter().map(|node_state| node_state.id)
+    }
+
+    /// Get the ids of all nodes that were shown as a row the last time the tree
+    /// was shown, in the order they are displayed.
+    ///
+    /// A node is not shown if one of its ancestors is closed or if it is flattened.
+    pub fn visible_nodes(&self) -> impl Iterator<Item = &NodeIdType> + '_ {
+        self.node_states
+            .iter()
+            .filter(|node_state| node_state.visible)
+            .map(|node_state| &node_state.id)
+    }
+
+    /// Get the screen rect the row of a node occupied the last time the tree was shown.
+    ///
+    /// `None` if the node was not shown.
+    pub fn node_rect(&self, id: NodeIdType) -> Option<Rect> {
+        self.node_state_of(&id)
+            .map(|node_state| node_state.row_rect)
+            .filter(|rect| rect.is_positive())
+    }
+
+    /// Get the node whose row was at the given screen position the last time the tree was shown.
+    pub fn node_at(&self, pos: Pos2) -> Option<NodeIdType> {
+        self.node_states
+            .iter()
+            .find(|node_state| node_state.visible && node_state.row_rect.contains(pos))
+            .map(|node_state| node_state.id)
+    }
+
+    /// Get the index of the row of a node among all [visible nodes](Self::visible_nodes).
+    ///
+    /// `None` if the node is not visible.
+    pub fn row_index_of(&self, id: NodeIdType) -> Option<usize> {
+        let index = self.node_index_of(&id)?;
+        if !self.node_states[index].visible {
+            return None;
+        }
+        Some(
+            self.node_states[..index]
+                .iter()
+                .filter(|node_state| node_state.visible)
+                .count(),
+        )
+    }
+
+    /// Get the ids of the direct children of a node in the order they were added.
+    pub fn children_of(&self, id: NodeIdType) -> impl Iterator<Item = NodeIdType> + '_ {
+        // Children are always added after their parent.
+        let start = self
+            .node_index_of(&id)
+            .map_or(self.node_states.len(), |i| i + 1);
+        self.node_states[start..]
+            .iter()
+            .filter(move |node_state| node_state.parent_id == Some(id))
+            .map(|node_state| node_state.id)
+    }
+
+    /// Get the ids of all ancestors of a node, starting with its parent.
+    pub fn ancestors(&self, id: NodeIdType) -> impl Iterator<Item = NodeIdType> + '_ {
+        std::iter::successors(self.parent_id_of(id), |parent_id| {
+            self.parent_id_of(*parent_id)
+        })
+    }
+
+    /// Whether or not the selected node is a descendant of this node.
+    pub fn contains_selection(&self, id: NodeIdType) -> bool {
+        self.selected
+            .is_some_and(|selected| self.ancestors(selected).any(|ancestor| ancestor == id))
+    }
+
+    /// Get the number of selected nodes in the subtree of a node, including the node itself.
+    ///
+    /// The subtree is taken from the parents of the nodes the last time the tree was shown.
+    pub fn selected_under(&self, id: NodeIdType) -> usize {
+        self.selected
+            .filter(|selected| *selected == id || self.contains_selection(id))
+            .map_or(0, |_| 1)
+    }
+
+    /// Get the path from the root of the tree to a node.
+    ///
+    /// The path starts with the root and ends with the node itself.
+    /// Empty if the node was not part of the tree the last time it was shown.
+    pub fn path_of(&self, id: NodeIdType) -> Vec<NodeIdType> {
+        if self.node_state_of(&id).is_none() {
+            return Vec::new();
+        }
+        let mut path = self.ancestors(id).collect::<Vec<_>>();
+        path.reverse();
+        path.push(id);
+        path
+    }
+
+    /// Get the depths of all descendants of a node relative to that node.
+    ///
+    /// Direct children have a depth of 1.
+    pub(crate) fn descendant_depths(&self, id: &NodeIdType) -> impl Iterator<Item = usize> + '_ {
+        let start = self
+            .node_index_of(id)
+            .map_or(self.node_states.len(), |index| index + 1);
+        // Node states are stored in the order they were build which means all
+        // nodes of a subtree directly follow the root of the subtree.
+        let mut ancestors = vec![*id];
+        self.node_states[start..]
+            .iter()
+            .map_while(move |node_state| {
+                while ancestors
+                    .last()
+                    .is_some_and(|ancestor| Some(*ancestor) != node_state.parent_id)
+                {
+                    ancestors.pop();
+                }
+                if ancestors.is_empty() {
+                    return None;
+                }
+                let depth = ancestors.len();
+                ancestors.push(node_state.id);
+                Some(depth)
+            })
+    }
+
+    /// Close all sibling directories of a node.
+    ///
+    /// Returns the ids of the directories that were closed.
+    pub(crate) fn close_siblings_of(&mut self, id: &NodeIdType) -> Vec<NodeIdType> {
+        let Some(parent_id) = self
+            .node_state_of(id)
+            .map(|node_state| node_state.parent_id)
+        else {
+            return Vec::new();
+        };
+        let mut closed = Vec::new();
+        for node_state in self.node_states.iter_mut() {
+            if node_state.parent_id == parent_id
+                && &node_state.id != id
+                && node_state.is_dir
+                && node_state.open
+                && !node_state.openness_locked
+            {
+                node_state.open = false;
+                closed.push(node_state.id);
+            }
+        }
+        closed
+    }
+
+    /// Take a snapshot of which directories are open and which node is selected.
+    ///
+    /// With the `serde` feature the snapshot can be saved independent of the
+    /// tree view state, e.g. as part of a project file.
+    pub fn snapshot(&self) -> TreeViewSnapshot<NodeIdType> {
+        TreeViewSnapshot {
+            dirs: self
+                .node_states
+                .iter()
+                .filter(|node_state| node_state.is_dir)
+                .map(|node_state| (node_state.id, node_state.open))
+                // Directories from an applied snapshot that were not added yet.
+                .chain(self.pending_openness.iter().map(|(id, open)| (*id, *open)))
+                .collect(),
+            selected: self.selected,
+        }
+    }
+
+    /// Restore which directories are open and which node is selected from a snapshot.
+    ///
+    /// Directories that are not part of the tree yet are opened or closed
+    /// as soon as they are added to the tree.
+    pub fn apply_snapshot(&mut self, snapshot: &TreeViewSnapshot<NodeIdType>) {
+        for (id, open) in snapshot.dirs.iter() {
+            if let Some(node_state) = self.node_state_of_mut(id) {
+                if !node_state.openness_locked {
+                    node_state.open = *open;
+                }
+            } else {
+                // Remember the open state until the directory is added to the tree.
+                self.pending_openness.insert(*id, *open);
+            }
+        }
+        self.selected = snapshot.selected;
+    }
+
+    /// Take a snapshot of the state with the node ids converted to strings.
+    ///
+    /// This allows the state to be stored without the node ids being serializable
+    /// and without the `persistence` feature. Restore it with [`Self::restore_with`].
+    pub fn save_with(
+        &self,
+        persistence: &impl StatePersistence<NodeIdType>,
+    ) -> TreeViewSnapshot<String> {
+        let snapshot = self.snapshot();
+        TreeViewSnapshot {
+            dirs: snapshot
+                .dirs
+                .iter()
+                .map(|(id, open)| (persistence.id_to_key(id), *open))
+                .collect(),
+            selected: snapshot.selected.map(|id| persistence.id_to_key(&id)),
+        }
+    }
+
+    /// Restore a snapshot that was taken with [`Self::save_with`].
+    ///
+    /// Keys that can not be converted back to node ids are ignored.
+    pub fn restore_with(
+        &mut self,
+        snapshot: &TreeViewSnapshot<String>,
+        persistence: &impl StatePersistence<NodeIdType>,
+    ) {
+        self.apply_snapshot(&TreeViewSnapshot {
+            dirs: snapshot
+                .dirs
+                .iter()
+                .filter_map(|(key, open)| persistence.key_to_id(key).map(|id| (id, *open)))
+                .collect(),
+            selected: snapshot
+                .selected
+                .as_ref()
+                .and_then(|key| persistence.key_to_id(key)),
+        });
+    }
+
+    /// Reserve capacity for at least `additional` more nodes.
+    ///
+    /// The tree view grows its internal storage as needed. Reserving capacity
+    /// up front avoids repeated reallocations when a large tree is first shown.
+    pub fn reserve(&mut self, additional: usize) {
+        self.node_states.reserve(additional);
+        self.node_index.reserve(additional);
+    }
+
+    /// Reset the parts of a restored state that should not be persisted.
+    #[cfg(feature = "persistence")]
+    fn apply_persist_mode(&mut self, persist: PersistMode) {
+        if !self.restored {
+            return;
+        }
+        self.restored = false;
+        match persist {
+            PersistMode::Nothing => *self = Self::default(),
+            PersistMode::OpennessOnly => {
+                self.selected = None;
+                self.secondary_selection = None;
+                self.dragged = None;
+            }
+            PersistMode::OpennessAndSelection => {
+                self.secondary_selection = None;
+                self.dragged = None;
+            }
+            PersistMode::All => (),
+        }
+    }
+
+    /// Replace the node states and rebuild the index of the node states.
+    pub(crate) fn set_node_states(&mut self, node_states: Vec<NodeState<NodeIdType>>) {
+        self.node_states = node_states;
+        self.node_index.clear();
+        self.node_index.extend(
+            self.node_states
+                .iter()
+                .enumerate()
+                .map(|(index, node_state)| (node_state.id, index)),
+        );
+    }
+
+    /// Get the index of the node state for an id.
+    pub(crate) fn node_index_of(&self, id: &NodeIdType) -> Option<usize> {
+        // The index is not persisted and is missing after the state was loaded.
+        // It can also be incomplete if the same id was used for multiple nodes.
+        if self.node_index.len() == self.node_states.len() {
+            self.node_index.get(id).copied()
+        } else {
+            self.node_states.iter().position(|ns| &ns.id == id)
+        }
+    }
+
     /// Get the node state for an id.
     pub(crate) fn node_state_of(&self, id: &NodeIdType) -> Option<&NodeState<NodeIdType>> {
-        self.node_states.iter().find(|ns| &ns.id == id)
+        self.node_index_of(id).map(|index| &self.node_states[index])
     }
     /// Get the node state for an id.
     pub(crate) fn node_state_of_mut(
         &mut self,
         id: &NodeIdType,
     ) -> Option<&mut NodeState<NodeIdType>> {
-        self.node_states.iter_mut().find(|ns| &ns.id == id)
+        self.node_index_of(id)
+            .map(|index| &mut self.node_states[index])
     }
 }
 
@@ -113,13 +793,51 @@ where
     NodeIdType: NodeId,
 {
     pub fn load(ui: &mut Ui, id: Id) -> Option<Self> {
-        ui.data_mut(|d| d.get_persisted(id))
+        ui.data_mut(|d| d.get_temp(id))
     }
 
     pub fn store(self, ui: &mut Ui, id: Id) {
-        ui.data_mut(|d| d.insert_persisted(id, self));
+        ui.data_mut(|d| d.insert_temp(id, self));
+    }
+
+    /// Take the state out of egui memory without cloning it.
+    ///
+    /// The memory is left with a default state until the state is stored again.
+    pub(crate) fn take(ui: &mut Ui, id: Id) -> Self {
+        ui.data_mut(|d| std::mem::take(d.get_temp_mut_or_default::<Self>(id)))
+    }
+
+    /// Load the state from egui's persisted data.
+    ///
+    /// Only which directories are open and which node is selected are stored.
+    /// `None` if no state was stored with [`Self::store_with`] for this id.
+    #[cfg(feature = "persistence")]
+    pub fn load_with(
+        ui: &mut Ui,
+        id: Id,
+        persistence: &impl StatePersistence<NodeIdType>,
+    ) -> Option<Self> {
+        let snapshot = ui.data_mut(|d| d.get_persisted::<TreeViewSnapshot<String>>(id))?;
+        let mut state = Self::default();
+        state.restore_with(&snapshot, persistence);
+        state.restored = true;
+        Some(state)
+    }
+
+    /// Store which directories are open and which node is selected
+    /// with egui's persisted data.
+    #[cfg(feature = "persistence")]
+    pub fn store_with(&self, ui: &mut Ui, id: Id, persistence: &impl StatePersistence<NodeIdType>) {
+        let snapshot = self.save_with(persistence);
+        ui.data_mut(|d| d.insert_persisted(id, snapshot));
     }
 }
+/// A state that is deserialized was restored from storage.
+#[cfg(feature = "persistence")]
+fn restored_default() -> bool {
+    true
+}
+
 /// State of the dragged node.
 #[derive(Clone)]
 #[cfg_attr(feature = "persistence", derive(serde::Serialize, serde::Deserialize))]
@@ -130,6 +848,11 @@ struct DragState<NodeIdType> {
     pub drag_row_offset: Vec2,
     /// Position of the pointer when the drag started.
     pub drag_start_pos: Pos2,
+    /// Time when the drag started.
+    pub drag_start_time: f64,
+    /// On a touch screen, a drag that moves before the touch was held for
+    /// the drag start delay is a swipe that scrolls instead of dragging the node.
+    pub swipe: bool,
     /// A drag only becomes valid after it has been dragged for
     /// a short distance.
     pub drag_valid: bool,
@@ -142,10 +865,40 @@ struct NodeState<NodeIdType> {
     id: NodeIdType,
     /// The parent node of this node.
     parent_id: Option<NodeIdType>,
+    /// Wether the node is a directory or not.
+    is_dir: bool,
     /// Wether the node is open or not.
     open: bool,
     /// Wether the node is visible or not.
     visible: bool,
+    /// Wether the node can be activated or not.
+    activatable: bool,
+    /// Number of pages of children that are shown for a paged directory.
+    child_pages: usize,
+    /// Height of the row the last time the node was shown.
+    row_height: f32,
+    /// Screen rect of the row of the node. [`Rect::NOTHING`] if it was not shown.
+    row_rect: Rect,
+    /// Top of the row relative to the top of the tree. `None` if it was not shown.
+    row_top: Option<f32>,
+    /// Height of the children of a directory the last time they were shown.
+    body_height: f32,
+    /// Wether the node can be navigated to with the keyboard.
+    focusable: bool,
+    /// Kind of the node set by the user.
+    kind: u32,
+    /// Wether the openness of the directory cannot be changed.
+    openness_locked: bool,
+    /// Wether nodes can be dropped on this node.
+    drop_allowed: bool,
+    /// The drop positions this node accepts.
+    allowed_drop_positions: DropPositionMask,
+}
+impl<NodeIdType> NodeState<NodeIdType> {
+    /// Whether or not the keyboard navigation can move to this node.
+    fn is_navigable(&self) -> bool {
+        self.visible && self.focusable
+    }
 }
 
 pub struct TreeView {
@@ -160,6 +913,15 @@ impl TreeView {
         }
     }
 
+    /// Set which parts of the state are restored when the state was loaded
+    /// from storage, e.g. after the app was restarted.
+    ///
+    /// Defaults to [`PersistMode::All`].
+    pub fn persist(mut self, persist: PersistMode) -> Self {
+        self.settings.persist = persist;
+        self
+    }
+
     /// Override the indent value from the current ui style with this value.
     ///
     /// If `None`, the value of the current ui style is used.
@@ -169,74 +931,641 @@ impl TreeView {
         self
     }
 
-    /// Set the style of the vline to show the indentation level.
-    pub fn vline_style(mut self, style: VLineStyle) -> Self {
-        self.settings.vline_style = style;
+    /// Limit how many levels of indentation are shown.
+    ///
+    /// Nodes that are nested deeper are drawn at this level of indentation
+    /// with a `…` in front of them. This keeps the labels of very deep trees visible.
+    /// The nodes keep their real depth for navigation and dropping.
+    /// Defaults to `None`.
+    pub fn max_indent_levels(mut self, max_indent_levels: Option<usize>) -> Self {
+        self.settings.max_indent_levels = max_indent_levels;
         self
     }
 
-    /// Set the row layout for this tree.
-    pub fn row_layout(mut self, layout: RowLayout) -> Self {
-        self.settings.row_layout = layout;
+    /// Wrap labels that are wider than the tree view.
+    ///
+    /// The row of a node with a wrapped label grows to fit all lines.
+    /// Use [`TreeViewState::row_height_of`] to get the height of a row.
+    /// Defaults to `false`.
+    pub fn wrap_labels(mut self, wrap_labels: bool) -> Self {
+        self.settings.wrap_labels = wrap_labels;
         self
     }
 
-    /// Set whether or not the tree should fill all available horizontal space.
-    ///
-    /// If the tree is part of a horizontally justified layout, this property has no
-    /// effect and the tree will always fill horizontal space.
+    /// Truncate labels that are wider than the tree view with a `…`.
     ///
-    /// Default is `true`.
-    pub fn fill_space_horizontal(mut self, fill_space_horizontal: bool) -> Self {
-        self.settings.fill_space_horizontal = fill_space_horizontal;
+    /// The complete text of a truncated label is shown when hovering it.
+    /// Labels are not truncated if [`TreeView::wrap_labels`] is set.
+    /// Defaults to `false`.
+    pub fn truncate_labels(mut self, truncate_labels: bool) -> Self {
+        self.settings.truncate_labels = truncate_labels;
         self
     }
 
-    /// Set whether or not the tree should fill all available vertical space.
-    ///
-    /// If the tree is part of a vertically justified layout, this property has no
-    /// effect and the tree will always fill vertical space.
+    /// Show the tree view inside its own vertical [`egui::ScrollArea`].
     ///
-    /// Default is `false`.
-    pub fn fill_space_vertical(mut self, fill_space_vertical: bool) -> Self {
-        self.settings.fill_space_vertical = fill_space_vertical;
+    /// The tree view then takes care of scrolling to nodes selected with the
+    /// keyboard and of scrolling while a node is dragged close to the top or bottom.
+    /// Leaves that are outside the visible area are not drawn.
+    /// The height of the scroll area is limited by [`TreeView::max_height`].
+    /// Defaults to `false`.
+    pub fn scroll(mut self, scroll: bool) -> Self {
+        self.settings.scroll = scroll;
         self
     }
 
-    /// Set the maximum width the tree can have.
+    /// Whether or not to show the position of the selected node on the scroll bar
+    /// when [`TreeView::scroll`] is used.
+    /// Defaults to `true`.
+    pub fn scroll_bar_markers(mut self, scroll_bar_markers: bool) -> Self {
+        self.settings.scroll_bar_markers = scroll_bar_markers;
+        self
+    }
+
+    /// Show a row with this text below open directories that have no children.
     ///
-    /// If the tree is part of a horizontally justified layout, this property has no
-    /// effect and the tree will always fill the available horizontal space.
-    pub fn max_width(mut self, width: f32) -> Self {
-        self.settings.max_width = width;
+    /// Dropping a node onto the placeholder row drops it into the directory.
+    /// By default no placeholder row is shown.
+    pub fn empty_dir_placeholder(mut self, text: impl Into<WidgetText>) -> Self {
+        self.settings.empty_dir_placeholder = Some(text.into());
         self
     }
 
-    /// Set the maximum hight the tree can have.
+    /// Set the text of the row that reveals the next page of children of a directory
+    /// with a [`child_page_size`](node::NodeBuilder::child_page_size).
     ///
-    /// If the tree is part of a vertical justified layout, this property has no
-    /// effect and the tree will always fill the available vertical space.
-    pub fn max_height(mut self, height: f32) -> Self {
-        self.settings.max_height = height;
+    /// The callback receives the number of children the next page reveals.
+    /// Default is `"Show {count} more…"`.
+    pub fn show_more_text(mut self, text: impl Fn(usize) -> WidgetText + 'static) -> Self {
+        self.settings.show_more_text = Box::new(text);
         self
     }
 
-    /// Set the minimum width the tree can have.
-    pub fn min_width(mut self, width: f32) -> Self {
-        self.settings.min_width = width;
+    /// Set the text of the row that is shown below directories whose children
+    /// are still [loading](node::NodeBuilder::loading).
+    ///
+    /// Default is `"Loading…"`.
+    pub fn loading_text(mut self, text: impl Into<WidgetText>) -> Self {
+        self.settings.loading_text = text.into();
         self
     }
 
-    /// Set the minimum hight the tree can have.
-    pub fn min_height(mut self, height: f32) -> Self {
-        self.settings.min_height = height;
+    /// Set where a node that was selected with the keyboard is placed in the
+    /// scroll area when [`TreeView::scroll`] is used.
+    ///
+    /// If `None`, the scroll area only scrolls as far as needed to show the node.
+    /// Defaults to `None`.
+    pub fn selection_scroll_align(mut self, align: Option<egui::Align>) -> Self {
+        self.settings.selection_scroll_align = align;
         self
     }
 
-    /// Start displaying the tree view.
+    /// Set how scrolling to a node that was selected with the keyboard is animated
+    /// when [`TreeView::scroll`] is used.
     ///
-    /// Construct the tree view using the [`TreeViewBuilder`] by adding
-    /// directories or leaves to the tree.
+    /// Use [`ScrollAnimation::none`] to jump to the node directly.
+    /// If `None`, the scroll animation of the current ui style is used.
+    /// Defaults to `None`.
+    pub fn selection_scroll_animation(mut self, animation: Option<ScrollAnimation>) -> Self {
+        self.settings.selection_scroll_animation = animation;
+        self
+    }
+
+    /// Set the style of the vline to show the indentation level.
+    pub fn vline_style(mut self, style: VLineStyle) -> Self {
+        self.settings.vline_style = style;
+        self
+    }
+
+    /// Set the row layout for this tree.
+    pub fn row_layout(mut self, layout: RowLayout) -> Self {
+        self.settings.row_layout = layout;
+        self
+    }
+
+    /// Set the horizontal alignment of the labels within their rows.
+    ///
+    /// Use [`egui::Align::Center`] to center the labels for a "card" like look.
+    /// The closer and icon are always placed in front of the label.
+    /// In right-to-left rows [`egui::Align::Min`] is the right side of the row.
+    ///
+    /// Rows are laid out right-to-left if the parent [`Ui`]'s layout is right-to-left.
+    ///
+    /// Default is [`egui::Align::Min`].
+    pub fn label_align(mut self, align: egui::Align) -> Self {
+        self.settings.label_align = Some(align);
+        self
+    }
+
+    /// Set which part of a row the selection background covers.
+    ///
+    /// Default is [`RowArea::FullRow`].
+    pub fn selection_area(mut self, area: RowArea) -> Self {
+        self.settings.selection_area = area;
+        self
+    }
+
+    /// Set how far the selection background is inset from the left and right
+    /// edge of its area.
+    ///
+    /// Together with [`TreeView::selection_rounding`] this gives pill shaped selections.
+    /// Default is `0.0`.
+    pub fn selection_inset(mut self, inset: f32) -> Self {
+        self.settings.selection_inset = inset;
+        self
+    }
+
+    /// Set the corner rounding of the selection background.
+    ///
+    /// If `None`, the rounding of active widgets is used.
+    /// Default is `None`.
+    pub fn selection_rounding(mut self, rounding: Option<Rounding>) -> Self {
+        self.settings.selection_rounding = rounding;
+        self
+    }
+
+    /// Show a gutter of `width` on the left of the rows.
+    ///
+    /// The callback returns the text that is shown in the gutter for each row.
+    /// For example `|row| Some(row.row_index.to_string())` shows the index of the
+    /// rows and `|row| row.child_count.map(|count| count.to_string())` shows the
+    /// number of children of the directories.
+    ///
+    /// Default is no gutter.
+    pub fn gutter(
+        mut self,
+        width: f32,
+        text: impl Fn(&GutterRow) -> Option<String> + 'static,
+    ) -> Self {
+        self.settings.gutter = Some(Gutter {
+            width,
+            text: Some(Box::new(text)),
+        });
+        self
+    }
+
+    /// Show a gutter of `width` on the left of the rows without any text.
+    ///
+    /// Use [`NodeBuilder::gutter_ui`](node::NodeBuilder::gutter_ui) to add widgets
+    /// like bookmarks or breakpoint markers to the gutter. The gutter does not move
+    /// with the indentation and clicking it does not select the node.
+    ///
+    /// Default is no gutter.
+    pub fn gutter_width(mut self, width: f32) -> Self {
+        self.settings.gutter = Some(Gutter { width, text: None });
+        self
+    }
+
+    /// Set whether or not the tree should fill all available horizontal space.
+    ///
+    /// If the tree is part of a horizontally justified layout, this property has no
+    /// effect and the tree will always fill horizontal space.
+    ///
+    /// Default is `true`.
+    pub fn fill_space_horizontal(mut self, fill_space_horizontal: bool) -> Self {
+        self.settings.fill_space_horizontal = fill_space_horizontal;
+        self
+    }
+
+    /// Set whether or not the tree should fill all available vertical space.
+    ///
+    /// If the tree is part of a vertically justified layout, this property has no
+    /// effect and the tree will always fill vertical space.
+    ///
+    /// Default is `false`.
+    pub fn fill_space_vertical(mut self, fill_space_vertical: bool) -> Self {
+        self.settings.fill_space_vertical = fill_space_vertical;
+        self
+    }
+
+    /// Set the maximum width the tree can have.
+    ///
+    /// If the tree is part of a horizontally justified layout, this property has no
+    /// effect and the tree will always fill the available horizontal space.
+    pub fn max_width(mut self, width: f32) -> Self {
+        self.settings.max_width = width;
+        self
+    }
+
+    /// Set the maximum hight the tree can have.
+    ///
+    /// If the tree is part of a vertical justified layout, this property has no
+    /// effect and the tree will always fill the available vertical space.
+    pub fn max_height(mut self, height: f32) -> Self {
+        self.settings.max_height = height;
+        self
+    }
+
+    /// Set the minimum width the tree can have.
+    pub fn min_width(mut self, width: f32) -> Self {
+        self.settings.min_width = width;
+        self
+    }
+
+    /// Set the minimum hight the tree can have.
+    pub fn min_height(mut self, height: f32) -> Self {
+        self.settings.min_height = height;
+        self
+    }
+
+    /// Set how far the pointer has to move while a node is pressed before
+    /// the node is dragged.
+    ///
+    /// Default is `5.0`.
+    pub fn drag_start_distance(mut self, distance: f32) -> Self {
+        self.settings.drag_start_distance = distance;
+        self
+    }
+
+    /// Set how long in seconds a node has to be pressed before it can be dragged.
+    ///
+    /// If the pointer moves further than the [`drag_start_distance`](TreeView::drag_start_distance)
+    /// before the delay has passed, the node is not dragged.
+    /// On a touch screen, swiping over the tree before the delay has passed
+    /// scrolls it instead of dragging the node, see [`TreeView::touch_scroll`].
+    /// A delay of about `0.3` works well for touch screens.
+    ///
+    /// Default is `0.0`.
+    pub fn drag_start_delay(mut self, delay: f32) -> Self {
+        self.settings.drag_start_delay = delay;
+        self
+    }
+
+    /// Set whether or not swiping over the tree on a touch screen scrolls it.
+    ///
+    /// A swipe that starts moving before the [`drag_start_delay`](TreeView::drag_start_delay)
+    /// has passed scrolls the scroll area the tree is in and keeps scrolling
+    /// for a moment after the finger is lifted.
+    /// If `false`, swiping over a node starts to drag it like with a mouse.
+    ///
+    /// Tapping a node selects it, double tapping activates it and a long press
+    /// opens its context menu.
+    ///
+    /// Default is `true`.
+    pub fn touch_scroll(mut self, touch_scroll: bool) -> Self {
+        self.settings.touch_scroll = touch_scroll;
+        self
+    }
+
+    /// Set whether or not dragging is restricted to reordering nodes within
+    /// their parent directory.
+    ///
+    /// If `true`, a dragged node can only be dropped before or after one
+    /// of its siblings. Dropping into other directories is not possible.
+    ///
+    /// Default is `false`.
+    pub fn drag_reorder_only(mut self, drag_reorder_only: bool) -> Self {
+        self.settings.drag_reorder_only = drag_reorder_only;
+        self
+    }
+
+    /// Set the maximum depth a node can have after it was dropped.
+    ///
+    /// Nodes at the top level of the tree have a depth of 0. The depth of the
+    /// dragged nodes children is taken into account. Drop positions that would
+    /// exceed this depth are not allowed.
+    ///
+    /// If `None`, the depth is not limited.
+    /// Default is `None`.
+    pub fn max_drop_depth(mut self, max_drop_depth: Option<usize>) -> Self {
+        self.settings.max_drop_depth = max_drop_depth;
+        self
+    }
+
+    /// Set the style of the badge that shows how many nodes are dragged.
+    ///
+    /// The badge is shown next to the dragged node. The children of a dragged
+    /// directory are not counted.
+    /// If `None`, no badge is shown.
+    ///
+    /// Default is `None`.
+    pub fn drag_badge(mut self, style: Option<DragBadgeStyle>) -> Self {
+        self.settings.drag_badge = style;
+        self
+    }
+
+    /// Set whether or not the dragged node is shown in its own viewport
+    /// once the pointer leaves the viewport of the tree.
+    ///
+    /// Without this, the dragged node is clipped to the viewport of the tree
+    /// which breaks up drags between the windows of a multi viewport app.
+    /// Has no effect if the backend does not support multiple viewports.
+    ///
+    /// Default is `false`.
+    pub fn drag_overlay_viewport(mut self, drag_overlay_viewport: bool) -> Self {
+        self.settings.drag_overlay_viewport = drag_overlay_viewport;
+        self
+    }
+
+    /// Set the style of the marker that shows where a dragged node will be dropped.
+    pub fn drop_marker_style(mut self, style: DropMarkerStyle) -> Self {
+        self.settings.drop_marker_style = style;
+        self
+    }
+
+    /// Override the color of the drop marker.
+    ///
+    /// If `None`, the selection color of the current ui style is used.
+    /// Defaults to `None`.
+    pub fn drop_marker_color(mut self, color: Option<Color32>) -> Self {
+        self.settings.drop_marker_color = color;
+        self
+    }
+
+    /// Set the thickness of the drop marker line.
+    ///
+    /// Default is `3.0`.
+    pub fn drop_marker_thickness(mut self, thickness: f32) -> Self {
+        self.settings.drop_marker_thickness = thickness;
+        self
+    }
+
+    /// Set whether or not a placeholder row is shown where the dragged node would be dropped.
+    ///
+    /// The placeholder moves the following rows down to preview the tree
+    /// after the drop. It is shown in addition to the drop marker.
+    ///
+    /// Default is `false`.
+    pub fn drop_preview(mut self, drop_preview: bool) -> Self {
+        self.settings.drop_preview = drop_preview;
+        self
+    }
+
+    /// Set which interactions activate a node.
+    ///
+    /// Only nodes that are [`activatable`](node::NodeBuilder::activatable) can be activated.
+    ///
+    /// Default is [`ActivateOn::DOUBLE_CLICK`] | [`ActivateOn::ENTER`].
+    pub fn activate_on(mut self, activate_on: ActivateOn) -> Self {
+        self.settings.activate_on = activate_on;
+        self
+    }
+
+    /// Set which part of a row activates the node when it is clicked.
+    ///
+    /// Default is [`RowArea::FullRow`].
+    pub fn activation_hit_area(mut self, area: RowArea) -> Self {
+        self.settings.activation_hit_area = area;
+        self
+    }
+
+    /// Set whether or not double clicking a directory that is activated by the
+    /// double click also opens or closes the directory.
+    ///
+    /// Directories that are not activated by a double click are always toggled.
+    ///
+    /// Default is `true`.
+    pub fn double_click_toggles_activatable(mut self, toggle: bool) -> Self {
+        self.settings.double_click_toggles_activatable = toggle;
+        self
+    }
+
+    /// Set whether or not clicking anywhere on the row of a directory
+    /// opens or closes the directory.
+    ///
+    /// If `false`, only the closer toggles the directory on a single click.
+    ///
+    /// Default is `false`.
+    pub fn toggle_on_row_click(mut self, toggle_on_row_click: bool) -> Self {
+        self.settings.toggle_on_row_click = toggle_on_row_click;
+        self
+    }
+
+    /// Set whether or not the user can interact with the nodes of the tree.
+    ///
+    /// If `false`, nodes cannot be selected, dragged, activated or show their
+    /// context menu and the tree does not take keyboard focus.
+    /// Directories can still be opened and closed.
+    ///
+    /// Default is `true`.
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.settings.interactive = interactive;
+        self
+    }
+
+    /// Set whether or not the tree view can receive keyboard focus.
+    ///
+    /// If `false`, the tree cannot be navigated with the keyboard.
+    /// See also [`TreeViewState::request_focus`].
+    ///
+    /// While the tree view has focus, tab and shift+tab move the focus to the
+    /// next and previous widget.
+    ///
+    /// Default is `true`.
+    pub fn focusable(mut self, focusable: bool) -> Self {
+        self.settings.focusable = focusable;
+        self
+    }
+
+    /// Set which keys the tree view handles itself while it has focus.
+    ///
+    /// Keys that are not part of the filter are left to the application and
+    /// to the focus navigation of egui.
+    ///
+    /// Default handles the horizontal and vertical arrows but not tab and escape.
+    pub fn focus_event_filter(mut self, filter: EventFilter) -> Self {
+        self.settings.focus_event_filter = filter;
+        self
+    }
+
+    /// Set whether or not an [`Action::NodeClicked`] is created for every click on a node.
+    ///
+    /// The click actions are created in addition to the other actions and can be
+    /// used to implement custom interactions with the nodes.
+    ///
+    /// Default is `false`.
+    pub fn report_clicks(mut self, report_clicks: bool) -> Self {
+        self.settings.report_clicks = report_clicks;
+        self
+    }
+
+    /// Set whether or not secondary clicking a node also selects it.
+    ///
+    /// If `false`, a secondary click only opens the context menu of the node
+    /// without changing the selection.
+    ///
+    /// Default is `false`.
+    pub fn secondary_click_selects(mut self, secondary_click_selects: bool) -> Self {
+        self.settings.secondary_click_selects = secondary_click_selects;
+        self
+    }
+
+    /// Set whether or not closed directories that contain the selected node
+    /// show a marker on the left of their row.
+    ///
+    /// See [`TreeViewState::contains_selection`].
+    ///
+    /// Default is `false`.
+    pub fn partial_selection_marker(mut self, partial_selection_marker: bool) -> Self {
+        self.settings.partial_selection_marker = partial_selection_marker;
+        self
+    }
+
+    /// Set whether or not the tree view is in accordion mode.
+    ///
+    /// In accordion mode, opening a directory closes all of its sibling directories
+    /// so that at most one directory is open per level. An [`Action::Collapsed`] is
+    /// created for each directory that was closed this way.
+    ///
+    /// Default is `false`.
+    pub fn accordion(mut self, accordion: bool) -> Self {
+        self.settings.accordion = accordion;
+        self
+    }
+
+    /// Set whether or not the root directory of the tree is hidden.
+    ///
+    /// The root directory is the first node that is added to the tree. If it is hidden
+    /// its children are shown at the top level of the tree. The root still exists as
+    /// the target for drops between its children and keeps its state.
+    ///
+    /// Use this to show a tree with multiple top level nodes. Without a root directory,
+    /// drops before or after the top level nodes are reported as [`Action::MoveToRoot`]
+    /// and [`Action::DragToRoot`].
+    ///
+    /// Default is `false`.
+    pub fn hide_root(mut self, hide_root: bool) -> Self {
+        self.settings.hide_root = hide_root;
+        self
+    }
+
+    /// Set whether or not the space below the last node of the tree is a drop zone.
+    ///
+    /// Dropping a node there moves it to the end of the root directory.
+    /// A strip of one row height is reserved below the last node for the drop zone.
+    /// Together with [`TreeView::fill_space_vertical`] the drop zone covers all
+    /// remaining space below the tree.
+    ///
+    /// Default is `false`.
+    pub fn root_drop_zone(mut self, root_drop_zone: bool) -> Self {
+        self.settings.root_drop_zone = root_drop_zone;
+        self
+    }
+
+    /// Set whether or not things dragged with [`egui::DragAndDrop`] can be dropped into the tree.
+    ///
+    /// This allows nodes to be dragged between different trees. Only a [`TreeDragPayload`]
+    /// with the id type of this tree is accepted. The drop creates an [`Action::DropExternal`]
+    /// and the payload can then be taken with [`egui::DragAndDrop::take_payload`].
+    ///
+    /// [`TreeView::show_state`] also works with ids that are not `Send + Sync + 'static`
+    /// which means the type of the payload cannot be checked. Trees shown with it accept
+    /// any payload.
+    ///
+    /// External drops are not accepted if the tree is not [interactive](TreeView::interactive).
+    ///
+    /// Default is `false`.
+    pub fn accept_external_drops(mut self, accept_external_drops: bool) -> Self {
+        self.settings.accept_external_drops = accept_external_drops;
+        self
+    }
+
+    /// Set whether or not directories show a closer.
+    ///
+    /// Without closers, directories can still be opened and closed by double clicking
+    /// them or with the keyboard. No space is reserved for closers.
+    ///
+    /// Default is `true`.
+    pub fn show_closers(mut self, show_closers: bool) -> Self {
+        self.settings.show_closers = show_closers;
+        self
+    }
+
+    /// Set when the closers of directories are visible.
+    ///
+    /// Hidden closers still react to clicks.
+    ///
+    /// Default is [`CloserVisibility::Always`].
+    pub fn closer_visibility(mut self, visibility: CloserVisibility) -> Self {
+        self.settings.closer_visibility = visibility;
+        self
+    }
+
+    /// Set on which side of the label the closer of a directory is shown.
+    ///
+    /// Default is [`CloserPosition::Left`].
+    pub fn closer_position(mut self, position: CloserPosition) -> Self {
+        self.settings.closer_position = position;
+        self
+    }
+
+    /// Set by how much the area that reacts to clicks on the closer is larger
+    /// than the closer itself.
+    ///
+    /// Default is `0.0`.
+    pub fn closer_hit_padding(mut self, padding: f32) -> Self {
+        self.settings.closer_hit_padding = padding;
+        self
+    }
+
+    /// Set whether or not the default closer is animated when a directory
+    /// is opened or closed.
+    ///
+    /// Default is `true`.
+    pub fn animate_closers(mut self, animate_closers: bool) -> Self {
+        self.settings.animate_closers = animate_closers;
+        self
+    }
+
+    /// Set whether or not the children of a directory slide and fade in when it
+    /// is opened and out when it is closed.
+    ///
+    /// Default is `false`.
+    pub fn animate_dirs(mut self, animate_dirs: bool) -> Self {
+        self.settings.animate_dirs = animate_dirs;
+        self
+    }
+
+    /// Set how long opening or closing a directory is animated in seconds.
+    ///
+    /// See [`TreeView::animate_dirs`].
+    /// Default is `0.1`.
+    pub fn dir_animation_duration(mut self, duration: f32) -> Self {
+        self.settings.dir_animation_duration = duration;
+        self
+    }
+
+    /// Set whether or not rows slide to their new position after a node was moved.
+    ///
+    /// The animation runs when the tree is shown after an [`Action::Move`] was
+    /// created and moves the rows whose position changed from where they were
+    /// shown before.
+    ///
+    /// Default is `false`.
+    pub fn animate_moves(mut self, animate_moves: bool) -> Self {
+        self.settings.animate_moves = animate_moves;
+        self
+    }
+
+    /// Set how long rows slide to their new position in seconds.
+    ///
+    /// See [`TreeView::animate_moves`].
+    /// Default is `0.2`.
+    pub fn move_animation_duration(mut self, duration: f32) -> Self {
+        self.settings.move_animation_duration = duration;
+        self
+    }
+
+    /// Set the maximum number of nodes the tree can have for animations to be enabled.
+    ///
+    /// Animating a node requires some bookkeeping from egui every frame. For large
+    /// trees this can be disabled by setting a limit.
+    ///
+    /// If `None`, animations are always enabled.
+    /// Default is `None`.
+    pub fn animation_node_limit(mut self, limit: Option<usize>) -> Self {
+        self.settings.animation_node_limit = limit;
+        self
+    }
+
+    /// Start displaying the tree view.
+    ///
+    /// Construct the tree view using the [`TreeViewBuilder`] by adding
+    /// directories or leaves to the tree.
+    ///
+    /// The state of the tree view is stored in egui memory which requires the
+    /// node ids to be `Send + Sync + 'static`. For other ids, e.g. borrowed
+    /// ids like `&str`, keep the state yourself and use [`TreeView::show_state`].
     pub fn show<NodeIdType>(
         self,
         ui: &mut Ui,
@@ -245,26 +1574,119 @@ impl TreeView {
     where
         NodeIdType: NodeId,
     {
-        let id = self.id;
-        let mut state = TreeViewState::load(ui, id).unwrap_or_default();
-        let res = self.show_state(ui, &mut state, build_tree_view);
-        state.store(ui, id);
-        res
+        self.show_with(ui, &mut (), build_tree_view)
     }
 
     /// Start displaying the tree view with a [`TreeViewState`].
     ///
     /// Construct the tree view using the [`TreeViewBuilder`] by addind
     /// directories or leaves to the tree.
+    ///
+    /// Unlike [`TreeView::show`], this works with any id that implements [`TreeViewId`],
+    /// including borrowed ids and ids that are not `Send`.
+    /// Dragging a node out of the tree does not set an [`egui::DragAndDrop`] payload
+    /// automatically, use [`TreeViewResponse::set_drag_payload`] for that.
     pub fn show_state<NodeIdType>(
+        self,
+        ui: &mut Ui,
+        state: &mut TreeViewState<NodeIdType>,
+        build_tree_view: impl FnMut(TreeViewBuilder<'_, '_, NodeIdType>),
+    ) -> TreeViewResponse<NodeIdType>
+    where
+        NodeIdType: TreeViewId,
+    {
+        self.show_state_with(ui, state, &mut (), build_tree_view)
+    }
+
+    /// Start displaying the tree view with a context.
+    ///
+    /// The context is passed to the `*_with` callbacks of the nodes, like
+    /// [`NodeBuilder::context_menu_with`](node::NodeBuilder::context_menu_with).
+    /// This allows the callbacks of all nodes to mutate the same application state
+    /// without having to share it between closures.
+    ///
+    /// See [`TreeView::show`].
+    pub fn show_with<NodeIdType, Ctx>(
+        self,
+        ui: &mut Ui,
+        ctx: &mut Ctx,
+        build_tree_view: impl FnMut(TreeViewBuilder<'_, '_, NodeIdType, Ctx>),
+    ) -> TreeViewResponse<NodeIdType>
+    where
+        NodeIdType: NodeId,
+    {
+        let id = self.id;
+        let mut state = TreeViewState::take(ui, id);
+        let external_payload =
+            egui::DragAndDrop::has_payload_of_type::<TreeDragPayload<NodeIdType>>(ui.ctx());
+        let res = self.show_state_impl(ui, &mut state, ctx, build_tree_view, external_payload);
+        state.store(ui, id);
+        res.set_drag_payload(ui.ctx());
+        res
+    }
+
+    /// Start displaying the tree view and store its state with egui's persisted data.
+    ///
+    /// Like [`TreeView::show`], but which directories are open and which node is
+    /// selected is also stored with [`TreeViewState::store_with`], so it survives a
+    /// restart of the app. `persistence` converts the node ids to strings,
+    /// so the node ids do not have to be serializable.
+    #[cfg(feature = "persistence")]
+    pub fn show_persisted<NodeIdType>(
+        self,
+        ui: &mut Ui,
+        persistence: &impl StatePersistence<NodeIdType>,
+        build_tree_view: impl FnMut(TreeViewBuilder<'_, '_, NodeIdType>),
+    ) -> TreeViewResponse<NodeIdType>
+    where
+        NodeIdType: NodeId,
+    {
+        let id = self.id;
+        let mut state = ui
+            .data_mut(|d| d.remove_temp::<TreeViewState<NodeIdType>>(id))
+            .or_else(|| TreeViewState::load_with(ui, id, persistence))
+            .unwrap_or_default();
+        let external_payload =
+            egui::DragAndDrop::has_payload_of_type::<TreeDragPayload<NodeIdType>>(ui.ctx());
+        let res = self.show_state_impl(ui, &mut state, &mut (), build_tree_view, external_payload);
+        state.store_with(ui, id, persistence);
+        state.store(ui, id);
+        res.set_drag_payload(ui.ctx());
+        res
+    }
+
+    /// Start displaying the tree view with a [`TreeViewState`] and a context.
+    ///
+    /// See [`TreeView::show_with`] and [`TreeView::show_state`].
+    pub fn show_state_with<NodeIdType, Ctx>(
+        self,
+        ui: &mut Ui,
+        state: &mut TreeViewState<NodeIdType>,
+        ctx: &mut Ctx,
+        build_tree_view: impl FnMut(TreeViewBuilder<'_, '_, NodeIdType, Ctx>),
+    ) -> TreeViewResponse<NodeIdType>
+    where
+        NodeIdType: TreeViewId,
+    {
+        // The payload type can only be checked for `'static` ids.
+        let external_payload = egui::DragAndDrop::has_any_payload(ui.ctx());
+        self.show_state_impl(ui, state, ctx, build_tree_view, external_payload)
+    }
+
+    fn show_state_impl<NodeIdType, Ctx>(
         mut self,
         ui: &mut Ui,
         state: &mut TreeViewState<NodeIdType>,
-        mut build_tree_view: impl FnMut(TreeViewBuilder<'_, '_, NodeIdType>),
+        ctx: &mut Ctx,
+        mut build_tree_view: impl FnMut(TreeViewBuilder<'_, '_, NodeIdType, Ctx>),
+        external_payload: bool,
     ) -> TreeViewResponse<NodeIdType>
     where
-        NodeIdType: TreeViewId + Send + Sync + 'static,
+        NodeIdType: TreeViewId,
     {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("TreeView::show_state").entered();
+
         // Justified layouts override these settings
         if ui.layout().horizontal_justify() {
             self.settings.fill_space_horizontal = true;
@@ -274,120 +1696,221 @@ impl TreeView {
             self.settings.fill_space_vertical = true;
             self.settings.max_height = f32::INFINITY;
         }
+        // Rows follow the text direction of the parent ui.
+        self.settings.right_to_left = ui.layout().main_dir() == egui::Direction::RightToLeft;
 
-        // Set the focus filter to get correct keyboard navigation while focused.
-        ui.memory_mut(|m| {
-            m.set_focus_lock_filter(
-                self.id,
-                EventFilter {
-                    tab: false,
-                    escape: false,
-                    horizontal_arrows: true,
-                    vertical_arrows: true,
-                },
-            )
-        });
+        let focusable = self.settings.focusable && self.settings.interactive;
+        if focusable {
+            if std::mem::take(&mut state.focus_requested) {
+                ui.memory_mut(|m| m.request_focus(self.id));
+            }
+            // Set the focus filter to get correct keyboard navigation while focused.
+            ui.memory_mut(|m| m.set_focus_lock_filter(self.id, self.settings.focus_event_filter));
+        } else {
+            state.focus_requested = false;
+            ui.memory_mut(|m| m.surrender_focus(self.id));
+        }
 
-        // Create the tree state by loading the previous frame and setting up the state.
-        let mut data = TreeViewData::new(ui, state, self.id);
-        let prev_selection = data.peristant.selected;
+        #[cfg(feature = "persistence")]
+        state.apply_persist_mode(self.settings.persist);
+        // Compare against the selection that was last reported so that changes to
+        // the selection made outside of the tree view also create an action.
+        let prev_selection = state.reported_selection;
 
-        // Calculate the desired size of the tree view widget.
-        let size = vec2(
-            if self.settings.fill_space_horizontal {
-                ui.available_width().at_most(self.settings.max_width)
-            } else {
-                data.peristant.size.x.at_most(self.settings.max_width)
+        // Create the tree state by loading the previous frame and build the tree.
+        let (mut data, used_rect) = if self.settings.scroll {
+            let scroll_area = egui::ScrollArea::vertical()
+                .id_salt(self.id.with("scroll area"))
+                .max_height(self.settings.max_height)
+                .show(ui, |ui| {
+                    let (mut data, used_rect) =
+                        self.build(ui, state, ctx, &mut build_tree_view, external_payload);
+                    scroll_while_dragging(ui, &mut data);
+                    (data, used_rect)
+                });
+            if self.settings.scroll_bar_markers {
+                let (data, used_rect) = &scroll_area.inner;
+                paint_scroll_bar_markers(ui, data, used_rect, &scroll_area.inner_rect);
             }
-            .at_least(self.settings.min_width),
-            if self.settings.fill_space_vertical {
-                ui.available_height().at_most(self.settings.max_height)
-            } else {
-                data.peristant.size.y.at_most(self.settings.max_height)
-            }
-            .at_least(self.settings.min_height),
-        );
+            scroll_area.inner
+        } else {
+            let (data, used_rect) =
+                self.build(ui, state, ctx, &mut build_tree_view, external_payload);
+            // Swipes scroll the scroll area the tree is in.
+            swipe_scroll(ui, data.peristant);
+            (data, used_rect)
+        };
 
-        // Run the build tree view closure
-        let used_rect = ui
-            .allocate_ui_with_layout(size, Layout::top_down(egui::Align::Min), |ui| {
-                ui.set_min_size(vec2(self.settings.min_width, self.settings.min_height));
-                ui.add_space(ui.spacing().item_spacing.y * 0.5);
-                build_tree_view(TreeViewBuilder::new(ui, &mut data, &self.settings));
-                // Add negative space because the place will add the item spacing on top of this.
-                ui.add_space(-ui.spacing().item_spacing.y * 0.5);
-
-                if self.settings.fill_space_horizontal {
-                    ui.set_min_width(ui.available_width());
-                }
-                if self.settings.fill_space_vertical {
-                    ui.set_min_height(ui.available_height());
-                }
-            })
-            .response
-            .rect;
+        data.stats.nodes_submitted = data.new_node_states.len();
+        data.stats.rows_hit_tested = data.rows_hit_tested.get();
+        data.stats.nodes_hidden =
+            data.stats.nodes_submitted - data.stats.nodes_rendered - data.stats.nodes_culled;
 
         // use new node states
-        data.peristant.node_states = data.new_node_states.clone();
+        data.peristant
+            .set_node_states(std::mem::take(&mut data.new_node_states));
+        // The rows were moved to their new positions by this frame.
+        data.peristant.moved = false;
+        // Only keep the label galleys of nodes that are still in the tree.
+        data.peristant.label_galleys = std::mem::take(&mut data.new_label_galleys);
 
         // If the tree was clicked it should receive focus.
         let tree_view_interact = data.interact(&used_rect);
-        if tree_view_interact.clicked || tree_view_interact.drag_started {
+        if focusable && (tree_view_interact.clicked || tree_view_interact.drag_started) {
             ui.memory_mut(|m| m.request_focus(self.id));
         }
 
-        if ui.memory(|m| m.has_focus(self.id)) {
+        let selected_before_input = data.peristant.selected;
+        data.peristant.has_focus = ui.memory(|m| m.has_focus(self.id));
+        if data.peristant.has_focus {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::trace_span!("handle_input").entered();
+
             // If the widget is focused but no node is selected we want to select any node
             // to allow navigating throught the tree.
             // In case we gain focus from a drag action we select the dragged node directly.
-            if data.peristant.selected.is_none() {
+            // When the focus was moved to the tree with tab, the previous selection is kept
+            // unless it cannot be navigated to anymore.
+            let gained_focus = data.interaction_response.gained_focus();
+            let selection_navigable = data
+                .peristant
+                .selected
+                .and_then(|id| data.peristant.node_state_of(&id))
+                .is_some_and(|node_state| node_state.is_navigable());
+            if data.peristant.selected.is_none() || (gained_focus && !selection_navigable) {
                 data.peristant.selected = data
                     .peristant
                     .dragged
                     .as_ref()
                     .map(|drag_state| drag_state.node_id)
-                    .or(data.peristant.node_states.first().map(|n| n.id));
+                    .filter(|id| data.peristant.can_select(*id))
+                    .or(data
+                        .peristant
+                        .node_states
+                        .iter()
+                        .find(|n| n.is_navigable() && data.peristant.can_select(n.id))
+                        .map(|n| n.id));
             }
+            // Number of rows that page up and page down move the selection by.
+            let row_height = data
+                .peristant
+                .selected
+                .and_then(|id| data.peristant.row_height_of(id))
+                .unwrap_or(ui.spacing().interact_size.y + ui.spacing().item_spacing.y);
+            let page_rows = (ui.clip_rect().intersect(used_rect).height() / row_height)
+                .floor()
+                .at_least(1.0) as usize;
+            let mut paste_requested = false;
             ui.input(|i| {
                 for event in i.events.iter() {
                     match event {
+                        Event::Key { .. } if !self.settings.focus_event_filter.matches(event) => (),
+                        // egui only sends a paste event if the clipboard has text in it.
+                        Event::Key {
+                            key: Key::V,
+                            pressed: true,
+                            modifiers,
+                            ..
+                        } if modifiers.command => paste_requested = true,
                         Event::Key { key, pressed, .. } if *pressed => {
-                            handle_input(data.peristant, key)
+                            handle_input(&mut data, &self.settings, key, page_rows)
+                        }
+                        Event::Copy => {
+                            if let Some(selected) = data.peristant.selected {
+                                data.actions.push(Action::Copy(selected));
+                                data.peristant.clipboard =
+                                    Some((selected, ClipboardOperation::Copy));
+                            }
                         }
+                        Event::Cut => {
+                            if let Some(selected) = data.peristant.selected {
+                                data.actions.push(Action::Cut(selected));
+                                data.peristant.clipboard =
+                                    Some((selected, ClipboardOperation::Cut));
+                            }
+                        }
+                        Event::Paste(_) => paste_requested = true,
                         _ => (),
                     }
                 }
             });
+            if paste_requested {
+                paste(&mut data);
+            }
+        }
+
+        // Keep the node selected with the keyboard in view.
+        let gained_focus = data.interaction_response.gained_focus();
+        if self.settings.scroll
+            && (data.peristant.selected != selected_before_input || gained_focus)
+        {
+            data.peristant.scroll_to_node = data.peristant.selected;
+        }
+
+        // Close the siblings of directories that were opened this frame.
+        if self.settings.accordion {
+            for id in std::mem::take(&mut data.opened_dirs) {
+                for closed_id in data.peristant.close_siblings_of(&id) {
+                    data.actions.push(Action::Collapsed(closed_id));
+                }
+            }
         }
         // Update the drag state
-        // A drag only becomes a valid drag after the pointer has traveled some distance.
+        // A drag only becomes a valid drag after the node was pressed for some time
+        // and the pointer has traveled some distance.
+        // Moving before the delay has passed cancels the drag. On a touch screen
+        // it is a swipe instead.
+        let mut drag_cancelled = false;
         if let Some(drag_state) = data.peristant.dragged.as_mut() {
-            if !drag_state.drag_valid {
-                drag_state.drag_valid = drag_state
+            if !drag_state.drag_valid && !drag_state.swipe {
+                let (pointer_pos, time, touch) =
+                    ui.input(|i| (i.pointer.latest_pos(), i.time, i.any_touches()));
+                let moved = drag_state
                     .drag_start_pos
-                    .distance(ui.ctx().pointer_latest_pos().unwrap_or_default())
-                    > 5.0;
+                    .distance(pointer_pos.unwrap_or_default())
+                    > self.settings.drag_start_distance;
+                let held = time - drag_state.drag_start_time;
+                let delay = self.settings.drag_start_delay as f64;
+                if touch && self.settings.touch_scroll && held < delay {
+                    drag_state.swipe = moved;
+                } else if held >= delay {
+                    drag_state.drag_valid = moved;
+                } else {
+                    drag_cancelled = moved;
+                }
             }
         }
+        if drag_cancelled {
+            data.peristant.dragged = None;
+        }
 
         // Create a drag or move action.
         if data.drag_valid() {
             if let Some((drag_state, (drop_id, position))) =
                 data.peristant.dragged.as_ref().zip(data.drop)
             {
-                if ui.ctx().input(|i| i.pointer.any_released()) {
-                    data.actions.push(Action::Move {
-                        source: drag_state.node_id,
-                        target: drop_id,
+                let source = drag_state.node_id;
+                let released = ui.ctx().input(|i| i.pointer.any_released());
+                data.peristant.moved = released;
+                data.actions.push(match (drop_id, released) {
+                    (Some(target), true) => Action::Move {
+                        source,
+                        target,
                         position,
-                    })
-                } else {
-                    data.actions.push(Action::Drag {
-                        source: drag_state.node_id,
-                        target: drop_id,
+                    },
+                    (Some(target), false) => Action::Drag {
+                        source,
+                        target,
                         position,
-                    })
-                }
+                    },
+                    (None, true) => Action::MoveToRoot { source, position },
+                    (None, false) => Action::DragToRoot { source, position },
+                });
+            }
+        }
+        if data.external_drag && ui.input(|i| i.pointer.any_released()) {
+            if let Some((target, position)) = data.drop {
+                data.actions.push(Action::DropExternal { target, position });
             }
         }
         // Create a selection action.
@@ -395,34 +1918,285 @@ impl TreeView {
             data.actions
                 .push(Action::SetSelected(data.peristant.selected));
         }
+        data.peristant.reported_selection = data.peristant.selected;
+
+        // Remember if the dragged node has left the tree so it can be dropped on other widgets.
+        let dragged_out = data
+            .peristant
+            .dragged
+            .as_ref()
+            .filter(|drag_state| drag_state.drag_valid)
+            .filter(|_| {
+                ui.ctx()
+                    .pointer_latest_pos()
+                    .is_some_and(|pos| !data.interaction_response.interact_rect.contains(pos))
+            })
+            .map(|drag_state| drag_state.node_id);
+
+        // Reset the drag state.
+        if ui.input(|i| i.pointer.button_released(egui::PointerButton::Primary)) {
+            data.peristant.dragged = None;
+        }
+        data.peristant.drop_target = if data.drag_valid() { data.drop } else { None };
+
+        // Remember the size of the tree for next frame.
+        data.peristant.size = used_rect.size();
+
+        // Remember the kinds of the nodes the actions refer to.
+        let kinds = data
+            .actions
+            .iter()
+            .flat_map(|action| match action {
+                Action::Move { source, target, .. } | Action::Drag { source, target, .. } => {
+                    vec![*source, *target]
+                }
+                Action::MoveToRoot { source, .. }
+                | Action::DragToRoot { source, .. }
+                | Action::Activate { id: source, .. } => vec![*source],
+                _ => Vec::new(),
+            })
+            .filter_map(|id| data.peristant.kind_of(id).map(|kind| (id, kind)))
+            .collect();
+
+        TreeViewResponse {
+            response: data.interaction_response,
+            drop_marker_idx: data.drop_marker_idx,
+            actions: data.actions,
+            stats: data.stats,
+            hovered: data.hovered,
+            drop_target: data.peristant.drop_target,
+            rect: used_rect,
+            previous_selection: prev_selection,
+            payloads: data.payloads,
+            kinds,
+            dragged_out,
+        }
+    }
+
+    /// Build the tree view.
+    ///
+    /// Returns the data of this frame and the rect the tree view used.
+    fn build<'state, NodeIdType: TreeViewId, Ctx>(
+        &self,
+        ui: &mut Ui,
+        state: &'state mut TreeViewState<NodeIdType>,
+        ctx: &mut Ctx,
+        build_tree_view: &mut impl FnMut(TreeViewBuilder<'_, '_, NodeIdType, Ctx>),
+        external_payload: bool,
+    ) -> (TreeViewData<'state, NodeIdType>, Rect) {
+        let mut data = TreeViewData::new(ui, state, self.id, &self.settings, external_payload);
+
+        // Create the actions for nodes that were activated through the state.
+        for id in std::mem::take(&mut data.peristant.pending_activations) {
+            data.actions.push(Action::Activate {
+                id,
+                method: ActivationMethod::Programmatic,
+            });
+        }
+
+        // Calculate the desired size of the tree view widget.
+        let size = vec2(
+            if self.settings.fill_space_horizontal {
+                ui.available_width().at_most(self.settings.max_width)
+            } else {
+                data.peristant.size.x.at_most(self.settings.max_width)
+            }
+            .at_least(self.settings.min_width),
+            if self.settings.fill_space_vertical {
+                ui.available_height().at_most(self.settings.max_height)
+            } else {
+                data.peristant.size.y.at_most(self.settings.max_height)
+            }
+            .at_least(self.settings.min_height),
+        );
+
+        // Run the build tree view closure
+        #[cfg(feature = "tracing")]
+        let build_start = web_time::Instant::now();
+        let used_rect = ui
+            .allocate_ui_with_layout(size, Layout::top_down(egui::Align::Min), |ui| {
+                ui.set_min_size(vec2(self.settings.min_width, self.settings.min_height));
+                ui.add_space(ui.spacing().item_spacing.y * 0.5);
+                {
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::trace_span!("build_tree_view").entered();
+                    let mut build = BuildState::new(ui, &data, &self.settings);
+                    build_tree_view(TreeViewBuilder::new(
+                        ui,
+                        &mut data,
+                        &self.settings,
+                        ctx,
+                        &mut build,
+                    ));
+                    TreeViewBuilder::new(ui, &mut data, &self.settings, ctx, &mut build).finish();
+                }
+                // Add negative space because the place will add the item spacing on top of this.
+                ui.add_space(-ui.spacing().item_spacing.y * 0.5);
+
+                if self.settings.fill_space_horizontal {
+                    ui.set_min_width(ui.available_width());
+                }
+                if self.settings.fill_space_vertical {
+                    ui.set_min_height(ui.available_height());
+                }
+            })
+            .response
+            .rect;
+        #[cfg(feature = "tracing")]
+        {
+            data.stats.build_time = build_start.elapsed();
+        }
+        (data, used_rect)
+    }
+}
 
-        // Reset the drag state.
-        if ui.input(|i| i.pointer.button_released(egui::PointerButton::Primary)) {
-            data.peristant.dragged = None;
-        }
+/// Paint markers on the scroll bar of the scroll area to show where the selected node is.
+fn paint_scroll_bar_markers<NodeIdType: TreeViewId>(
+    ui: &mut Ui,
+    data: &TreeViewData<NodeIdType>,
+    content_rect: &Rect,
+    inner_rect: &Rect,
+) {
+    if content_rect.height() <= inner_rect.height() {
+        return;
+    }
+    let Some(selected_row) = data.selected_row else {
+        return;
+    };
+    // Find the position of the scroll bar the same way the scroll area does.
+    let scroll = &ui.spacing().scroll;
+    let x_range = if scroll.floating {
+        let right = inner_rect.right() - scroll.bar_outer_margin;
+        Rangef::new(right - scroll.bar_width, right)
+    } else {
+        let left = inner_rect.right() + scroll.bar_inner_margin;
+        Rangef::new(left, left + scroll.bar_width)
+    };
+    let y = remap(
+        selected_row.center(),
+        content_rect.y_range(),
+        inner_rect.y_range(),
+    );
+    let marker = Rect::from_x_y_ranges(x_range, y - 1.5..=y + 1.5);
+    ui.painter()
+        .rect_filled(marker, 0.0, ui.visuals().selection.bg_fill);
+}
 
-        // Remember the size of the tree for next frame.
-        data.peristant.size = used_rect.size();
+/// Scroll the surrounding scroll area while a node is dragged close to its edges
+/// or while the tree is swiped on a touch screen.
+fn scroll_while_dragging<NodeIdType: TreeViewId>(ui: &mut Ui, data: &mut TreeViewData<NodeIdType>) {
+    if swipe_scroll(ui, data.peristant) {
+        return;
+    }
+    if !data.drag_valid() {
+        return;
+    }
+    let Some(pointer_pos) = ui.ctx().pointer_latest_pos() else {
+        return;
+    };
+    let clip_rect = ui.clip_rect();
+    let margin = 2.0 * ui.spacing().interact_size.y;
+    let depth = if pointer_pos.y < clip_rect.top() + margin {
+        clip_rect.top() + margin - pointer_pos.y
+    } else if pointer_pos.y > clip_rect.bottom() - margin {
+        clip_rect.bottom() - margin - pointer_pos.y
+    } else {
+        return;
+    };
+    // Scroll faster the closer the pointer is to the edge.
+    const MAX_SPEED: f32 = 600.0;
+    let speed = MAX_SPEED * (depth / margin).clamp(-1.0, 1.0);
+    ui.scroll_with_delta(vec2(0.0, speed * ui.input(|i| i.stable_dt)));
+    ui.ctx().request_repaint();
+}
 
-        TreeViewResponse {
-            response: data.interaction_response,
-            drop_marker_idx: data.drop_marker_idx,
-            actions: data.actions,
-        }
+/// Scroll the scroll area the tree is in while the tree is swiped on a touch screen.
+///
+/// After the swipe is released, the scrolling continues with the velocity of the swipe
+/// and slows down the same way as a [`egui::ScrollArea`] does.
+/// Returns `true` if the tree was scrolled.
+fn swipe_scroll<NodeIdType>(ui: &mut Ui, state: &mut TreeViewState<NodeIdType>) -> bool {
+    /// Deceleration of the scrolling after a swipe in points per second squared.
+    const FRICTION: f32 = 1000.0;
+
+    let (delta, velocity, pressed, dt) = ui.input(|i| {
+        (
+            i.pointer.delta().y,
+            i.pointer.velocity().y,
+            i.pointer.any_pressed(),
+            i.stable_dt.at_most(0.1),
+        )
+    });
+    if state
+        .dragged
+        .as_ref()
+        .is_some_and(|drag_state| drag_state.swipe)
+    {
+        ui.scroll_with_delta(vec2(0.0, delta));
+        state.swipe_velocity = velocity;
+        return true;
+    }
+    if pressed || state.swipe_velocity == 0.0 {
+        state.swipe_velocity = 0.0;
+        return false;
+    }
+    ui.scroll_with_delta(vec2(0.0, state.swipe_velocity * dt));
+    let friction = FRICTION * dt;
+    state.swipe_velocity = if state.swipe_velocity.abs() > friction {
+        state.swipe_velocity - friction * state.swipe_velocity.signum()
+    } else {
+        0.0
+    };
+    ui.ctx().request_repaint();
+    true
+}
+
+/// Paste the node on the clipboard relative to the selected node.
+fn paste<NodeIdType: TreeViewId>(data: &mut TreeViewData<NodeIdType>) {
+    let state = &mut *data.peristant;
+    let Some(((source, operation), selected)) = state.clipboard.zip(state.selected) else {
+        return;
+    };
+    // Paste into a selected directory or after a selected leaf.
+    let (target, position) = if state.node_state_of(&selected).is_some_and(|n| n.is_dir) {
+        (Some(selected), DropPosition::Last)
+    } else {
+        (state.parent_id_of(selected), DropPosition::After(selected))
+    };
+    if !state.paste_allowed(source, target, &position) {
+        return;
+    }
+    // A cut node can only be pasted once.
+    if operation == ClipboardOperation::Cut {
+        state.clipboard = None;
     }
+    data.actions.push(Action::Paste {
+        source,
+        target,
+        position,
+        operation,
+    });
 }
 
-fn handle_input<NodeIdType: TreeViewId>(state: &mut TreeViewState<NodeIdType>, key: &Key) {
+// The bounds checks stay inside the arms of the keys they belong to.
+#[allow(clippy::collapsible_match)]
+fn handle_input<NodeIdType: TreeViewId>(
+    data: &mut TreeViewData<NodeIdType>,
+    settings: &TreeViewSettings,
+    key: &Key,
+    page_rows: usize,
+) {
+    let state = &mut *data.peristant;
     let Some(selected_id) = &state.selected else {
         return;
     };
-    let Some(selected_index) = state
-        .node_states
-        .iter()
-        .position(|ns| &ns.id == selected_id)
-    else {
+    let Some(selected_index) = state.node_index_of(selected_id) else {
         return;
     };
+    let policy = &state.selection_policy;
+    let selectable = |node: &NodeState<NodeIdType>| {
+        node.is_navigable() && policy.as_ref().map_or(true, |policy| policy(node.id))
+    };
     let node_state = &mut state.node_states[selected_index];
 
     match key {
@@ -433,7 +2207,7 @@ fn handle_input<NodeIdType: TreeViewId>(state: &mut TreeViewState<NodeIdType>, k
                     state.node_states[0..selected_index]
                         .iter()
                         .rev()
-                        .find(|node| node.visible)
+                        .find(|node| selectable(node))
                 {
                     state.selected = Some(node.id);
                 }
@@ -444,17 +2218,69 @@ fn handle_input<NodeIdType: TreeViewId>(state: &mut TreeViewState<NodeIdType>, k
                 // Search for previous visible node.
                 if let Some(node) = state.node_states[(selected_index + 1)..]
                     .iter()
-                    .find(|node| node.visible)
+                    .find(|node| selectable(node))
                 {
                     state.selected = Some(node.id);
                 }
             }
         }
+        Key::Home => {
+            if let Some(node) = state.node_states.iter().find(|node| selectable(node)) {
+                state.selected = Some(node.id);
+            }
+        }
+        Key::End => {
+            if let Some(node) = state.node_states.iter().rev().find(|node| selectable(node)) {
+                state.selected = Some(node.id);
+            }
+        }
+        Key::PageUp => {
+            // Move up by a page or to the first visible node.
+            if let Some(node) = state.node_states[0..selected_index]
+                .iter()
+                .rev()
+                .filter(|node| selectable(node))
+                .take(page_rows)
+                .last()
+            {
+                state.selected = Some(node.id);
+            }
+        }
+        Key::PageDown => {
+            // Move down by a page or to the last visible node.
+            if let Some(node) = state.node_states[(selected_index + 1)..]
+                .iter()
+                .filter(|node| selectable(node))
+                .take(page_rows)
+                .last()
+            {
+                state.selected = Some(node.id);
+            }
+        }
+        Key::Enter
+            if node_state.activatable && settings.activate_on.contains(ActivateOn::ENTER) =>
+        {
+            data.actions.push(Action::Activate {
+                id: node_state.id,
+                method: ActivationMethod::EnterKey,
+            });
+        }
         Key::ArrowLeft => {
-            if node_state.open {
+            if node_state.open && !node_state.openness_locked {
                 node_state.open = false;
-            } else if node_state.parent_id.is_some() {
-                state.selected = node_state.parent_id;
+            } else {
+                // Select the closest ancestor that can be navigated to and selected.
+                // Nodes at the top level of a tree with a hidden root stay selected.
+                let mut parent_id = node_state.parent_id;
+                while let Some(parent) = parent_id.and_then(|id| state.node_state_of(&id)) {
+                    if selectable(parent) {
+                        break;
+                    }
+                    parent_id = parent.parent_id;
+                }
+                if parent_id.is_some() {
+                    state.selected = parent_id;
+                }
             }
         }
         Key::ArrowRight => {
@@ -463,13 +2289,16 @@ fn handle_input<NodeIdType: TreeViewId>(state: &mut TreeViewState<NodeIdType>, k
                     // Search for previous visible node.
                     if let Some(node) = state.node_states[(selected_index + 1)..]
                         .iter()
-                        .find(|node| node.visible)
+                        .find(|node| selectable(node))
                     {
                         state.selected = Some(node.id);
                     }
                 }
-            } else {
+            } else if !node_state.openness_locked {
                 node_state.open = true;
+                if node_state.is_dir {
+                    data.opened_dirs.push(node_state.id);
+                }
             }
         }
         _ => (),
@@ -486,7 +2315,8 @@ struct TreeViewData<'state, NodeIdType> {
     /// Response of the interaction.
     interaction_response: Response,
     /// NodeId and Drop position of the drop target.
-    drop: Option<(NodeIdType, DropPosition<NodeIdType>)>,
+    /// The id is `None` for the top level of a tree without a root directory.
+    drop: Option<(Option<NodeIdType>, DropPosition<NodeIdType>)>,
     /// Shape index of the drop marker
     drop_marker_idx: ShapeIdx,
     /// Wether or not the tree view has keyboard focus.
@@ -495,9 +2325,40 @@ struct TreeViewData<'state, NodeIdType> {
     actions: Vec<Action<NodeIdType>>,
     /// New node states for when this frame is done.
     new_node_states: Vec<NodeState<NodeIdType>>,
+    /// Height of the subtree of the dragged node.
+    dragged_subtree_height: usize,
+    /// Number of nodes that are dragged.
+    dragged_node_count: usize,
+    /// Directories that were opened by the user this frame.
+    opened_dirs: Vec<NodeIdType>,
+    /// Galleys of plain text labels that were used this frame.
+    new_label_galleys: HashMap<NodeIdType, (Id, Arc<Galley>)>,
+    /// Values attached to the nodes this frame.
+    payloads: HashMap<NodeIdType, Box<dyn Any>>,
+    /// Wether or not animations are enabled for this frame.
+    animations_enabled: bool,
+    /// Statistics for this frame.
+    stats: TreeViewStats,
+    /// Number of rects that were hit tested this frame.
+    /// Nodes hit test through a shared reference, so this can not be part of `stats`.
+    rows_hit_tested: Cell<usize>,
+    /// The node the pointer is hovering over.
+    hovered: Option<NodeIdType>,
+    /// Vertical range of the row of the selected node.
+    selected_row: Option<Rangef>,
+    /// Only positions inside this rect can interact with the nodes.
+    interact_clip: Rect,
+    /// Whether an [`egui::DragAndDrop`] payload from outside the tree is dragged over it.
+    external_drag: bool,
 }
-impl<'state, NodeIdType> TreeViewData<'state, NodeIdType> {
-    fn new(ui: &mut Ui, state: &'state mut TreeViewState<NodeIdType>, id: Id) -> Self {
+impl<'state, NodeIdType: TreeViewId> TreeViewData<'state, NodeIdType> {
+    fn new(
+        ui: &mut Ui,
+        state: &'state mut TreeViewState<NodeIdType>,
+        id: Id,
+        settings: &TreeViewSettings,
+        external_payload: bool,
+    ) -> Self {
         let interaction_response = interact_no_expansion(
             ui,
             Rect::from_min_size(ui.cursor().min, state.size),
@@ -505,6 +2366,22 @@ impl<'state, NodeIdType> TreeViewData<'state, NodeIdType> {
             Sense::click_and_drag(),
         );
         let has_focus = ui.memory(|m| m.has_focus(id));
+        let dragged_subtree_height = state
+            .dragged
+            .as_ref()
+            .and_then(|drag_state| state.descendant_depths(&drag_state.node_id).max())
+            .unwrap_or(0);
+        // Only a single node can be dragged at a time.
+        let dragged_node_count = usize::from(state.dragged.is_some());
+        let external_drag = settings.accept_external_drops
+            && settings.interactive
+            && state.dragged.is_none()
+            && external_payload
+            && interaction_response.contains_pointer();
+        let new_node_states = Vec::with_capacity(state.node_states.capacity());
+        let animations_enabled = settings
+            .animation_node_limit
+            .map_or(true, |limit| state.node_states.len() <= limit);
 
         TreeViewData {
             peristant: state,
@@ -513,16 +2390,29 @@ impl<'state, NodeIdType> TreeViewData<'state, NodeIdType> {
             interaction_response,
             has_focus,
             actions: Vec::new(),
-            new_node_states: Vec::new(),
+            new_node_states,
+            dragged_subtree_height,
+            dragged_node_count,
+            opened_dirs: Vec::new(),
+            new_label_galleys: HashMap::new(),
+            payloads: HashMap::new(),
+            animations_enabled,
+            stats: TreeViewStats::default(),
+            rows_hit_tested: Cell::new(0),
+            hovered: None,
+            selected_row: None,
+            interact_clip: Rect::EVERYTHING,
+            external_drag,
         }
     }
 }
 impl<NodeIdType: TreeViewId> TreeViewData<'_, NodeIdType> {
     pub fn interact(&self, rect: &Rect) -> Interaction {
+        self.rows_hit_tested.set(self.rows_hit_tested.get() + 1);
         if !self
             .interaction_response
             .hover_pos()
-            .is_some_and(|pos| rect.contains(pos))
+            .is_some_and(|pos| rect.contains(pos) && self.interact_clip.contains(pos))
         {
             return Interaction {
                 clicked: false,
@@ -546,10 +2436,32 @@ impl<NodeIdType: TreeViewId> TreeViewData<'_, NodeIdType> {
     /// Is the current drag valid.
     /// `false` if no drag is currently registered.
     pub fn drag_valid(&self) -> bool {
+        self.external_drag
+            || self
+                .peristant
+                .dragged
+                .as_ref()
+                .is_some_and(|drag_state| drag_state.drag_valid)
+    }
+
+    /// Position of the pointer to drop the dragged node at.
+    ///
+    /// The tree is not hovered while something is dragged in from outside
+    /// of it, so the position is taken from the pointer directly.
+    pub fn drop_pos(&self) -> Option<Pos2> {
+        if self.external_drag {
+            self.interaction_response.ctx.pointer_hover_pos()
+        } else {
+            self.interaction_response.hover_pos()
+        }
+    }
+    /// Get the parent id of the dragged node.
+    /// `None` if no node is dragged or the dragged node has no parent.
+    pub fn dragged_parent_id(&self) -> Option<NodeIdType> {
         self.peristant
             .dragged
             .as_ref()
-            .is_some_and(|drag_state| drag_state.drag_valid)
+            .and_then(|drag_state| self.peristant.parent_id_of(drag_state.node_id))
     }
     /// Is the given id part of a valid drag.
     pub fn is_dragged(&self, id: &NodeIdType) -> bool {
@@ -559,6 +2471,12 @@ impl<NodeIdType: TreeViewId> TreeViewData<'_, NodeIdType> {
             .is_some_and(|drag_state| drag_state.drag_valid && &drag_state.node_id == id)
     }
 
+    pub fn is_cut(&self, id: &NodeIdType) -> bool {
+        self.peristant.clipboard.is_some_and(|(cut_id, operation)| {
+            operation == ClipboardOperation::Cut && &cut_id == id
+        })
+    }
+
     pub fn is_selected(&self, id: &NodeIdType) -> bool {
         self.peristant.selected.as_ref().is_some_and(|n| n == id)
     }
@@ -571,6 +2489,7 @@ impl<NodeIdType: TreeViewId> TreeViewData<'_, NodeIdType> {
     }
 }
 
+#[derive(Clone, Copy)]
 struct Interaction {
     pub clicked: bool,
     pub double_clicked: bool,
@@ -602,32 +2521,257 @@ pub enum DropPosition<NodeIdType> {
     Last,
     After(NodeIdType),
     Before(NodeIdType),
+    /// The dragged item is dropped onto a leaf that allows dropping.
+    /// The container is the parent directory of that leaf. A leaf at the top level
+    /// of a tree without a root directory has no container and the drop is reported
+    /// with [`Action::MoveToRoot`] and [`Action::DragToRoot`].
+    On(NodeIdType),
+}
+
+/// A set of drop positions a node accepts.
+///
+/// Masks can be combined with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DropPositionMask(u8);
+impl DropPositionMask {
+    /// No drop positions are allowed.
+    pub const NONE: Self = Self(0);
+    /// Dropping into a directory ([`DropPosition::First`] and [`DropPosition::Last`])
+    /// or onto a leaf ([`DropPosition::On`]).
+    pub const INTO: Self = Self(1 << 0);
+    /// Dropping before a child of a directory ([`DropPosition::Before`]).
+    pub const BEFORE: Self = Self(1 << 1);
+    /// Dropping after a child of a directory ([`DropPosition::After`]).
+    pub const AFTER: Self = Self(1 << 2);
+    /// All drop positions are allowed.
+    pub const ALL: Self = Self(Self::INTO.0 | Self::BEFORE.0 | Self::AFTER.0);
+
+    /// Whether all positions of `other` are contained in this mask.
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The mask that contains a drop position.
+    pub fn of<NodeIdType>(position: &DropPosition<NodeIdType>) -> Self {
+        match position {
+            DropPosition::First | DropPosition::Last | DropPosition::On(_) => Self::INTO,
+            DropPosition::Before(_) => Self::BEFORE,
+            DropPosition::After(_) => Self::AFTER,
+        }
+    }
+}
+impl Default for DropPositionMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+impl std::ops::BitOr for DropPositionMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A set of interactions that activate a node.
+///
+/// Sets can be combined with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ActivateOn(u8);
+impl ActivateOn {
+    /// Nodes are never activated by the user.
+    pub const NONE: Self = Self(0);
+    /// Nodes are activated when they are clicked.
+    pub const SINGLE_CLICK: Self = Self(1 << 0);
+    /// Nodes are activated when they are double clicked.
+    pub const DOUBLE_CLICK: Self = Self(1 << 1);
+    /// Nodes are activated when enter is pressed while they are selected.
+    pub const ENTER: Self = Self(1 << 2);
+
+    /// Whether all interactions of `other` are contained in this set.
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+impl Default for ActivateOn {
+    fn default() -> Self {
+        Self::DOUBLE_CLICK | Self::ENTER
+    }
+}
+impl std::ops::BitOr for ActivateOn {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The side of the label the closer of a directory is shown on.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloserPosition {
+    /// The closer is shown in front of the icon and label.
+    #[default]
+    Left,
+    /// The closer is shown after the label.
+    Right,
+}
+
+/// When the closers of directories are visible.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloserVisibility {
+    /// Closers are always visible.
+    #[default]
+    Always,
+    /// Closers are only visible while the pointer is over the tree.
+    TreeHovered,
+    /// A closer is only visible while the pointer is over its row.
+    RowHovered,
+}
+
+/// A part of a row in the tree.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowArea {
+    /// The whole width of the row.
+    #[default]
+    FullRow,
+    /// Only the icon and the label of the row.
+    LabelOnly,
 }
 
 struct TreeViewSettings {
+    persist: PersistMode,
     override_indent: Option<f32>,
+    max_indent_levels: Option<usize>,
+    wrap_labels: bool,
+    truncate_labels: bool,
+    scroll: bool,
+    scroll_bar_markers: bool,
+    empty_dir_placeholder: Option<WidgetText>,
+    show_more_text: Box<ShowMoreText>,
+    loading_text: WidgetText,
+    selection_scroll_align: Option<egui::Align>,
+    selection_scroll_animation: Option<ScrollAnimation>,
     vline_style: VLineStyle,
     row_layout: RowLayout,
+    label_align: Option<egui::Align>,
+    right_to_left: bool,
+    selection_area: RowArea,
+    selection_inset: f32,
+    selection_rounding: Option<Rounding>,
+    gutter: Option<Gutter>,
     max_width: f32,
     max_height: f32,
     min_width: f32,
     min_height: f32,
     fill_space_horizontal: bool,
     fill_space_vertical: bool,
+    drag_start_distance: f32,
+    drag_start_delay: f32,
+    touch_scroll: bool,
+    drag_reorder_only: bool,
+    max_drop_depth: Option<usize>,
+    drag_badge: Option<DragBadgeStyle>,
+    drag_overlay_viewport: bool,
+    drop_marker_style: DropMarkerStyle,
+    drop_marker_color: Option<Color32>,
+    drop_marker_thickness: f32,
+    drop_preview: bool,
+    activate_on: ActivateOn,
+    activation_hit_area: RowArea,
+    double_click_toggles_activatable: bool,
+    toggle_on_row_click: bool,
+    interactive: bool,
+    focusable: bool,
+    focus_event_filter: EventFilter,
+    report_clicks: bool,
+    secondary_click_selects: bool,
+    partial_selection_marker: bool,
+    accordion: bool,
+    hide_root: bool,
+    show_closers: bool,
+    closer_position: CloserPosition,
+    closer_visibility: CloserVisibility,
+    closer_hit_padding: f32,
+    root_drop_zone: bool,
+    accept_external_drops: bool,
+    animate_closers: bool,
+    animate_dirs: bool,
+    dir_animation_duration: f32,
+    animate_moves: bool,
+    move_animation_duration: f32,
+    animation_node_limit: Option<usize>,
 }
 
 impl Default for TreeViewSettings {
     fn default() -> Self {
         Self {
+            persist: Default::default(),
             override_indent: None,
+            max_indent_levels: None,
+            wrap_labels: false,
+            truncate_labels: false,
+            scroll: false,
+            scroll_bar_markers: true,
+            empty_dir_placeholder: None,
+            show_more_text: Box::new(|count| format!("Show {count} more…").into()),
+            loading_text: "Loading…".into(),
+            selection_scroll_align: None,
+            selection_scroll_animation: None,
             vline_style: Default::default(),
             row_layout: Default::default(),
+            label_align: None,
+            right_to_left: false,
+            selection_area: Default::default(),
+            selection_inset: 0.0,
+            selection_rounding: None,
+            gutter: None,
             max_width: f32::INFINITY,
             max_height: f32::INFINITY,
             min_width: 0.0,
             min_height: 0.0,
             fill_space_horizontal: true,
             fill_space_vertical: false,
+            drag_start_distance: 5.0,
+            drag_start_delay: 0.0,
+            touch_scroll: true,
+            drag_reorder_only: false,
+            max_drop_depth: None,
+            drag_badge: None,
+            drag_overlay_viewport: false,
+            drop_marker_style: Default::default(),
+            drop_marker_color: None,
+            drop_marker_thickness: 3.0,
+            drop_preview: false,
+            activate_on: Default::default(),
+            activation_hit_area: Default::default(),
+            double_click_toggles_activatable: true,
+            toggle_on_row_click: false,
+            interactive: true,
+            focusable: true,
+            focus_event_filter: EventFilter {
+                tab: false,
+                escape: false,
+                horizontal_arrows: true,
+                vertical_arrows: true,
+            },
+            report_clicks: false,
+            secondary_click_selects: false,
+            partial_selection_marker: false,
+            accordion: false,
+            hide_root: false,
+            show_closers: true,
+            closer_position: Default::default(),
+            closer_visibility: Default::default(),
+            closer_hit_padding: 0.0,
+            root_drop_zone: false,
+            accept_external_drops: false,
+            animate_closers: true,
+            animate_dirs: false,
+            dir_animation_duration: 0.1,
+            animate_moves: false,
+            move_animation_duration: 0.2,
+            animation_node_limit: None,
         }
     }
 }
@@ -644,6 +2788,58 @@ pub enum VLineStyle {
     Hook,
 }
 
+/// Which parts of the [`TreeViewState`] are restored when it was loaded from storage.
+///
+/// This only has an effect with the `persistence` feature, for states loaded
+/// with [`TreeViewState::load_with`] or deserialized by the app.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PersistMode {
+    /// Nothing is restored.
+    Nothing,
+    /// Only which directories are open and which nodes are pinned is restored.
+    OpennessOnly,
+    /// Which directories are open, which nodes are pinned and the selected node is restored.
+    OpennessAndSelection,
+    /// The entire state is restored.
+    #[default]
+    All,
+}
+
+/// Style of the marker that shows where a dragged node will be dropped.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum DropMarkerStyle {
+    /// A line spanning the full width of the tree is drawn between the nodes.
+    #[default]
+    Line,
+    /// The entire target directory is highlighted.
+    RowHighlight,
+    /// A line starting at the indentation level of the drop position is drawn
+    /// with a small caret at its start.
+    Caret,
+}
+
+/// Style of the badge that shows the number of dragged nodes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DragBadgeStyle {
+    /// Fill color of the badge.
+    /// If `None`, the selection background color of the ui is used.
+    pub fill: Option<Color32>,
+    /// Color of the number.
+    /// If `None`, the selection stroke color of the ui is used.
+    pub text_color: Option<Color32>,
+    /// Font size of the number.
+    pub font_size: f32,
+}
+impl Default for DragBadgeStyle {
+    fn default() -> Self {
+        Self {
+            fill: None,
+            text_color: None,
+            font_size: 11.0,
+        }
+    }
+}
+
 /// How rows in the tree are layed out.
 ///
 /// Each row in the tree is made up of three elements. A closer,
@@ -689,16 +2885,212 @@ pub enum Action<NodeIdType> {
         target: NodeIdType,
         position: DropPosition<NodeIdType>,
     },
+    /// Move a node to the top level of a tree that has no root directory.
+    ///
+    /// Only created for trees with multiple nodes at the top level,
+    /// see [`TreeView::hide_root`].
+    MoveToRoot {
+        source: NodeIdType,
+        position: DropPosition<NodeIdType>,
+    },
+    /// Like [`Action::Drag`] but the node would be dropped at the top level
+    /// of a tree that has no root directory.
+    DragToRoot {
+        source: NodeIdType,
+        position: DropPosition<NodeIdType>,
+    },
+    /// A directory was closed because one of its sibling directories
+    /// was opened in accordion mode.
+    Collapsed(NodeIdType),
+    /// A node was activated.
+    Activate {
+        /// Id of the activated node.
+        id: NodeIdType,
+        /// How the node was activated.
+        method: ActivationMethod,
+    },
+    /// The node was copied with the copy shortcut while the tree view had focus.
+    ///
+    /// Use [`TreeViewResponse::copied`] or [`TreeViewResponse::copy_text`]
+    /// to put text for it on the clipboard.
+    Copy(NodeIdType),
+    /// The node was cut with the cut shortcut while the tree view had focus.
+    ///
+    /// The node is drawn dimmed until it is pasted.
+    Cut(NodeIdType),
+    /// A node was clicked with a pointer button.
+    ///
+    /// Only created if [`TreeView::report_clicks`] is enabled.
+    NodeClicked {
+        /// Id of the clicked node.
+        id: NodeIdType,
+        /// The button that clicked the node.
+        button: egui::PointerButton,
+        /// The modifiers that were held down during the click.
+        modifiers: egui::Modifiers,
+        /// Whether the click was the second click of a double click.
+        double: bool,
+    },
+    /// Something was dragged in from outside of the tree and dropped.
+    ///
+    /// Only created if [`TreeView::accept_external_drops`] is enabled.
+    /// Use [`egui::DragAndDrop::take_payload`] to get what was dropped.
+    DropExternal {
+        /// The directory the payload is dropped into.
+        /// `None` for the top level of a tree without a root directory.
+        target: Option<NodeIdType>,
+        /// The position inside the target directory.
+        position: DropPosition<NodeIdType>,
+    },
+    /// A node that was cut or copied was pasted with the paste shortcut.
+    ///
+    /// The node is pasted into the selected directory or after the selected leaf.
+    /// Nodes are never pasted where [`TreeViewState::paste_allowed`] forbids it.
+    Paste {
+        /// The node that was cut or copied.
+        source: NodeIdType,
+        /// The directory the node is pasted into.
+        /// `None` for the top level of a tree without a root directory.
+        target: Option<NodeIdType>,
+        /// The position inside the target directory.
+        position: DropPosition<NodeIdType>,
+        /// Whether the node was cut or copied.
+        operation: ClipboardOperation,
+    },
+}
+
+/// Decides whether the user can select a node.
+type SelectionPolicy<NodeIdType> = Arc<dyn Fn(NodeIdType) -> bool + Send + Sync>;
+
+/// The kinds of the nodes an action refers to.
+///
+/// See [`TreeViewResponse::kinds`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NodeKinds {
+    /// Kind of the moved, dragged or activated node.
+    pub source: u32,
+    /// Kind of the directory the node is moved or dragged into.
+    /// `None` for [`Action::Activate`] and for moves to the top level of the tree.
+    pub target: Option<u32>,
+}
+
+/// The [`egui::DragAndDrop`] payload of nodes that are dragged out of a tree view.
+///
+/// With the `serde` feature the payload can be serialized to pass it
+/// between applications.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TreeDragPayload<NodeIdType> {
+    /// Id of the tree view the nodes are dragged from.
+    pub source: Id,
+    /// Ids of the dragged nodes.
+    pub ids: Vec<NodeIdType>,
+}
+
+/// A row of the tree view that the text of the gutter is created for.
+///
+/// See [`TreeView::gutter`].
+#[derive(Debug, Clone, Copy)]
+pub struct GutterRow {
+    /// Index of the row among all [visible nodes](TreeViewState::visible_nodes).
+    pub row_index: usize,
+    /// The indentation level of the row.
+    pub depth: usize,
+    /// Number of children of a directory. `None` for leaves.
+    pub child_count: Option<usize>,
+}
+
+/// Creates the text of the gutter for a row.
+type GutterText = dyn Fn(&GutterRow) -> Option<String>;
+
+/// Creates the text of the row that reveals more children of a directory.
+type ShowMoreText = dyn Fn(usize) -> WidgetText;
+
+/// The gutter on the left of the rows.
+struct Gutter {
+    width: f32,
+    text: Option<Box<GutterText>>,
+}
+
+/// How a node was put on the clipboard of the tree view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardOperation {
+    /// The node was cut. Cut nodes are drawn dimmed until they are pasted.
+    Cut,
+    /// The node was copied.
+    Copy,
+}
+
+/// How a node was activated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivationMethod {
+    /// The node was clicked.
+    SingleClick,
+    /// The node was double clicked.
+    DoubleClick,
+    /// Enter was pressed while the node was selected.
+    EnterKey,
+    /// The node was activated with [`TreeViewState::activate`].
+    Programmatic,
+}
+
+/// Which directories of a tree view are open and which node is selected.
+///
+/// Created with [`TreeViewState::snapshot`] and restored with [`TreeViewState::apply_snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TreeViewSnapshot<NodeIdType> {
+    /// Each directory and whether it is open.
+    pub dirs: Vec<(NodeIdType, bool)>,
+    /// The selected node.
+    pub selected: Option<NodeIdType>,
+}
+
+/// Statistics about how the tree view was shown in a frame.
+///
+/// Use this to find out why a tree view is slow to show.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TreeViewStats {
+    /// Number of nodes that were added to the tree.
+    pub nodes_submitted: usize,
+    /// Number of nodes whose row was shown.
+    pub nodes_rendered: usize,
+    /// Number of nodes whose row was not drawn because it is outside of the visible area.
+    pub nodes_culled: usize,
+    /// Number of nodes that were added but not shown because a parent directory
+    /// is closed, the node is flattened or it is not on a shown page.
+    pub nodes_hidden: usize,
+    /// Number of rows, closers and drop zones that were tested for pointer interaction.
+    pub rows_hit_tested: usize,
+    /// Time it took to run the closure that builds the tree.
+    ///
+    /// Only measured with the `tracing` feature, otherwise this is zero.
+    pub build_time: Duration,
 }
 
 pub struct TreeViewResponse<NodeIdType> {
     pub response: Response,
     /// Actions this tree view would like to perform.
     pub actions: Vec<Action<NodeIdType>>,
+    /// Statistics about how the tree view was shown this frame.
+    pub stats: TreeViewStats,
+    /// The node the pointer is hovering over.
+    pub hovered: Option<NodeIdType>,
+    /// The target and position the dragged node would be dropped to.
+    /// See [`TreeViewState::drop_target`].
+    pub drop_target: Option<(Option<NodeIdType>, DropPosition<NodeIdType>)>,
+    /// The rectangle the tree view occupied this frame.
+    pub rect: Rect,
+    /// The node that was selected before this frame.
+    pub previous_selection: Option<NodeIdType>,
     // /// If a row was dragged in the tree this will contain information about
     // /// who was dragged to who and at what position.
     // pub drag_drop_action: Option<DragDropAction<NodeIdType>>,
     drop_marker_idx: ShapeIdx,
+    payloads: HashMap<NodeIdType, Box<dyn Any>>,
+    kinds: HashMap<NodeIdType, u32>,
+    dragged_out: Option<NodeIdType>,
 }
 impl<NodeIdType: TreeViewId> TreeViewResponse<NodeIdType> {
     /// Remove the drop marker from the tree view.
@@ -708,6 +3100,118 @@ impl<NodeIdType: TreeViewId> TreeViewResponse<NodeIdType> {
     pub fn remove_drop_marker(&self, ui: &mut Ui) {
         ui.painter().set(self.drop_marker_idx, Shape::Noop);
     }
+
+    /// Get the value that was attached to a node with
+    /// [`NodeBuilder::payload`](node::NodeBuilder::payload) this frame.
+    ///
+    /// Returns `None` if the node has no payload or the payload is not of type `T`.
+    pub fn payload<T: Any>(&self, id: &NodeIdType) -> Option<&T> {
+        self.payloads.get(id)?.downcast_ref()
+    }
+
+    /// Get the kinds of the nodes a move, drag or [`Action::Activate`] refers to. Set the kind of a node with [`NodeBuilder::kind`](node::NodeBuilder::kind).
+    ///
+    /// Returns `None` for all other actions.
+    pub fn kinds(&self, action: &Action<NodeIdType>) -> Option<NodeKinds> {
+        let kind_of = |id: &NodeIdType| self.kinds.get(id).copied().unwrap_or(0);
+        match action {
+            Action::Move { source, target, .. } | Action::Drag { source, target, .. } => {
+                Some(NodeKinds {
+                    source: kind_of(source),
+                    target: Some(kind_of(target)),
+                })
+            }
+            Action::MoveToRoot { source, .. }
+            | Action::DragToRoot { source, .. }
+            | Action::Activate { id: source, .. } => Some(NodeKinds {
+                source: kind_of(source),
+                target: None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The node that is dragged while the pointer is outside of the tree.
+    pub fn dragged_out(&self) -> Option<NodeIdType> {
+        self.dragged_out
+    }
+
+    /// Set the node that is dragged out of the tree as a [`TreeDragPayload`]
+    /// for [`egui::DragAndDrop`].
+    ///
+    /// Other widgets can then accept the node with the standard egui apis, e.g.
+    /// [`Response::dnd_release_payload::<TreeDragPayload<NodeIdType>>`](Response::dnd_release_payload).
+    /// [`TreeView::show`] does this automatically.
+    pub fn set_drag_payload(&self, ctx: &egui::Context)
+    where
+        NodeIdType: Send + Sync + 'static,
+    {
+        if let Some(id) = self.dragged_out {
+            egui::DragAndDrop::set_payload(
+                ctx,
+                TreeDragPayload {
+                    source: self.response.id,
+                    ids: vec![id],
+                },
+            );
+        }
+    }
+
+    /// Iterate over all [`Action::Move`] actions as `(source, target, position)`.
+    pub fn moves(
+        &self,
+    ) -> impl Iterator<Item = (NodeIdType, NodeIdType, DropPosition<NodeIdType>)> + '_ {
+        self.actions.iter().filter_map(|action| match action {
+            Action::Move {
+                source,
+                target,
+                position,
+            } => Some((*source, *target, *position)),
+            _ => None,
+        })
+    }
+
+    /// Iterate over all [`Action::Activate`] actions as `(id, method)`.
+    pub fn activations(&self) -> impl Iterator<Item = (NodeIdType, ActivationMethod)> + '_ {
+        self.actions.iter().filter_map(|action| match action {
+            Action::Activate { id, method } => Some((*id, *method)),
+            _ => None,
+        })
+    }
+
+    /// Return the new selection if the selection was changed this frame.
+    pub fn selection_changed(&self) -> Option<Option<NodeIdType>> {
+        self.actions.iter().find_map(|action| match action {
+            Action::SetSelected(selected) => Some(*selected),
+            _ => None,
+        })
+    }
+
+    /// Get the previously selected node and the newly selected node
+    /// if the selection changed this frame.
+    pub fn selection_diff(&self) -> Option<(Option<NodeIdType>, Option<NodeIdType>)> {
+        self.selection_changed()
+            .map(|selected| (self.previous_selection, selected))
+    }
+
+    /// Iterate over the nodes that were copied or cut this frame.
+    pub fn copied(&self) -> impl Iterator<Item = NodeIdType> + '_ {
+        self.actions.iter().filter_map(|action| match action {
+            Action::Copy(id) | Action::Cut(id) => Some(*id),
+            _ => None,
+        })
+    }
+
+    /// Put text on the clipboard for the nodes that were copied or cut this frame.
+    ///
+    /// `text_of` provides the text for a copied node. The text of multiple nodes is
+    /// put on separate lines.
+    pub fn copy_text(&self, ui: &Ui, mut text_of: impl FnMut(NodeIdType) -> String) {
+        let lines = self.copied().map(&mut text_of).collect::<Vec<_>>();
+        if !lines.is_empty() {
+            ui.ctx().copy_text(lines.join("\n"));
+        }
+    }
 }
 
 /// Interact with the ui without egui adding any extra space.
@@ -718,3 +3222,199 @@ fn interact_no_expansion(ui: &mut Ui, rect: Rect, id: Id, sense: Sense) -> Respo
     *ui.spacing_mut() = spacing_before;
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Show a tree view with the given state for one frame.
+    fn show(state: &mut TreeViewState<u32>, mut build: impl FnMut(&mut TreeViewBuilder<u32>)) {
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                TreeView::new(Id::new("tree"))
+                    .show_state(ui, state, |mut builder| build(&mut builder));
+            });
+        });
+    }
+
+    /// ```text
+    /// 0
+    /// ├ 1
+    /// │ ├ 2
+    /// │ └ 3
+    /// └ 4
+    /// ```
+    fn example_tree(builder: &mut TreeViewBuilder<u32>) {
+        builder.dir(0, "root");
+        builder.dir(1, "dir");
+        builder.leaf(2, "a");
+        builder.leaf(3, "b");
+        builder.close_dir();
+        builder.leaf(4, "c");
+        builder.close_dir();
+    }
+
+    fn closed(dirs: &[u32]) -> TreeViewSnapshot<u32> {
+        TreeViewSnapshot {
+            dirs: dirs.iter().map(|id| (*id, false)).collect(),
+            selected: None,
+        }
+    }
+
+    #[test]
+    fn remap_ids_keeps_state() {
+        let mut state = TreeViewState::default();
+        state.apply_snapshot(&closed(&[1]));
+        show(&mut state, example_tree);
+        state.set_selected(Some(2));
+        state.pin(2);
+
+        state.remap_ids(&HashMap::from([(1, 11), (2, 12)]));
+
+        assert_eq!(state.selected(), Some(12));
+        assert_eq!(state.pinned(), &[12]);
+        assert_eq!(state.is_open(11), Some(false));
+        assert_eq!(state.is_open(1), None);
+        assert_eq!(state.parent_id_of(12), Some(11));
+        assert_eq!(state.path_of(3), vec![0, 11, 3]);
+    }
+
+    #[test]
+    fn rename_id_renames_one_node() {
+        let mut state = TreeViewState::default();
+        show(&mut state, example_tree);
+
+        state.rename_id(4, 40);
+
+        assert_eq!(state.parent_id_of(40), Some(0));
+        assert_eq!(state.parent_id_of(4), None);
+        assert_eq!(state.iter_nodes().collect::<Vec<_>>(), vec![0, 1, 2, 3, 40]);
+    }
+
+    #[test]
+    fn apply_snapshot_to_shown_dirs() {
+        let mut state = TreeViewState::default();
+        show(&mut state, example_tree);
+        assert_eq!(state.is_open(1), Some(true));
+
+        state.apply_snapshot(&TreeViewSnapshot {
+            dirs: vec![(1, false)],
+            selected: Some(4),
+        });
+
+        assert_eq!(state.is_open(1), Some(false));
+        assert_eq!(state.selected(), Some(4));
+    }
+
+    #[test]
+    fn apply_snapshot_to_dirs_added_later() {
+        let mut state = TreeViewState::default();
+        state.apply_snapshot(&closed(&[1, 5]));
+        // Directory 5 is not part of the tree for a few frames.
+        show(&mut state, example_tree);
+        show(&mut state, example_tree);
+        assert_eq!(state.is_open(1), Some(false));
+        assert_eq!(state.is_open(5), None);
+        assert!(state.snapshot().dirs.contains(&(5, false)));
+
+        show(&mut state, |builder| {
+            example_tree(builder);
+            builder.dir(5, "late");
+            builder.close_dir();
+        });
+
+        assert_eq!(state.is_open(5), Some(false));
+        // The directory is only part of the snapshot once it was applied.
+        let dirs = state.snapshot().dirs;
+        assert_eq!(dirs.iter().filter(|(id, _)| *id == 5).count(), 1);
+    }
+
+    #[test]
+    fn apply_snapshot_does_not_add_nodes() {
+        let mut state = TreeViewState::default();
+        state.apply_snapshot(&closed(&[5]));
+
+        assert_eq!(state.iter_nodes().count(), 0);
+        assert_eq!(state.path_of(5), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn find_next_and_prev_wrap_around() {
+        let mut state = TreeViewState::default();
+        show(&mut state, example_tree);
+        let is_leaf = |id: &u32| [2, 3, 4].contains(id);
+
+        assert_eq!(state.find_next(is_leaf), Some(2));
+        assert_eq!(state.find_prev(is_leaf), Some(4));
+
+        state.set_selected(Some(3));
+        assert_eq!(state.find_next(is_leaf), Some(4));
+        assert_eq!(state.find_prev(is_leaf), Some(2));
+
+        state.set_selected(Some(4));
+        assert_eq!(state.find_next(is_leaf), Some(2));
+        assert_eq!(state.find_next(|id| *id == 4), Some(4));
+        assert_eq!(state.find_next(|id| *id == 9), None);
+    }
+
+    #[test]
+    fn path_of_nodes() {
+        let mut state = TreeViewState::default();
+        show(&mut state, example_tree);
+
+        assert_eq!(state.path_of(0), vec![0]);
+        assert_eq!(state.path_of(3), vec![0, 1, 3]);
+        assert_eq!(state.path_of(4), vec![0, 4]);
+        assert_eq!(state.path_of(9), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn row_index_of_skips_hidden_nodes() {
+        let mut state = TreeViewState::default();
+        show(&mut state, example_tree);
+        assert_eq!(state.row_index_of(3), Some(3));
+        assert_eq!(state.row_index_of(4), Some(4));
+
+        state.apply_snapshot(&closed(&[1]));
+        show(&mut state, example_tree);
+
+        assert_eq!(state.row_index_of(1), Some(1));
+        assert_eq!(state.row_index_of(3), None);
+        assert_eq!(state.row_index_of(4), Some(2));
+        assert_eq!(state.row_index_of(9), None);
+    }
+
+    #[test]
+    fn paste_into_itself_or_descendant_is_not_allowed() {
+        let mut state = TreeViewState::default();
+        show(&mut state, example_tree);
+
+        assert!(!state.paste_allowed(1, Some(1), &DropPosition::Last));
+        assert!(!state.paste_allowed(0, Some(1), &DropPosition::Last));
+        assert!(!state.paste_allowed(0, Some(1), &DropPosition::After(2)));
+        assert!(state.paste_allowed(2, Some(1), &DropPosition::Last));
+        assert!(state.paste_allowed(1, Some(0), &DropPosition::After(4)));
+        assert!(state.paste_allowed(4, Some(1), &DropPosition::First));
+        assert!(state.paste_allowed(1, None, &DropPosition::After(0)));
+    }
+
+    #[test]
+    fn paste_respects_allowed_drop_positions() {
+        let mut state = TreeViewState::default();
+        show(&mut state, |builder| {
+            builder.dir(0, "root");
+            builder.node(node::NodeBuilder::dir(1).allowed_drop_positions(DropPositionMask::INTO));
+            builder.leaf(2, "a");
+            builder.close_dir();
+            builder.node(node::NodeBuilder::dir(3).drop_allowed(false));
+            builder.close_dir();
+            builder.close_dir();
+        });
+
+        assert!(state.paste_allowed(2, Some(1), &DropPosition::Last));
+        assert!(!state.paste_allowed(0, Some(1), &DropPosition::Last));
+        assert!(!state.paste_allowed(2, Some(1), &DropPosition::After(2)));
+        assert!(!state.paste_allowed(2, Some(3), &DropPosition::Last));
+    }
+}
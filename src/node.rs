@@ -1,27 +1,56 @@
+use std::any::Any;
+
 use egui::{
-    emath, epaint, remap, vec2, CursorIcon, Id, InnerResponse, LayerId, Order, Rangef, Rect,
-    Response, Shape, Stroke, Ui, UiBuilder, Vec2,
+    emath, epaint, remap, vec2, Align, Align2, CentralPanel, Color32, CursorIcon, FontId, Frame,
+    Id, InnerResponse, LayerId, Layout, NumExt, Order, Pos2, Rangef, Rect, Response, Sense, Shape,
+    Stroke, TextStyle, TextWrapMode, Ui, UiBuilder, Vec2, ViewportBuilder, ViewportId, WidgetText,
 };
 
-use crate::{Interaction, RowLayout, TreeViewData, TreeViewId, TreeViewSettings};
+use crate::{
+    CloserPosition, CloserVisibility, DragBadgeStyle, DropPositionMask, Interaction, RowLayout,
+    TreeViewData, TreeViewId, TreeViewSettings,
+};
 
 pub type AddUi<'add_ui> = dyn FnMut(&mut Ui) + 'add_ui;
 pub type AddCloser<'add_ui> = dyn FnMut(&mut Ui, CloserState) + 'add_ui;
-
-pub struct NodeBuilder<'add_ui, NodeIdType> {
+pub type AddUiWith<'add_ui, Ctx> = dyn FnMut(&mut Ui, &mut Ctx) + 'add_ui;
+pub type AddCloserWith<'add_ui, Ctx> = dyn FnMut(&mut Ui, CloserState, &mut Ctx) + 'add_ui;
+
+/// A node in the tree view.
+///
+/// `Ctx` is the type of the context that is passed to the `*_with` callbacks of the node
+/// when the tree is shown with [`TreeView::show_with`](crate::TreeView::show_with).
+pub struct NodeBuilder<'add_ui, NodeIdType, Ctx = ()> {
     pub(crate) id: NodeIdType,
     pub(crate) is_dir: bool,
     pub(crate) flatten: bool,
     pub(crate) is_open: bool,
     pub(crate) default_open: bool,
     pub(crate) drop_allowed: bool,
+    pub(crate) allowed_drop_positions: DropPositionMask,
+    pub(crate) activatable: bool,
+    pub(crate) child_page_size: Option<usize>,
+    pub(crate) visible: bool,
+    pub(crate) focusable: bool,
+    pub(crate) loading: bool,
+    pub(crate) children_hint: Option<usize>,
+    pub(crate) child_count: Option<usize>,
+    pub(crate) payload: Option<Box<dyn Any>>,
+    pub(crate) kind: u32,
+    pub(crate) show_closer: bool,
+    pub(crate) always_open: bool,
+    #[cfg(feature = "icons")]
+    default_icon: Option<crate::icons::IconKind>,
+    extra_indent: usize,
     indent: usize,
-    icon: Option<Box<AddUi<'add_ui>>>,
-    closer: Option<Box<AddCloser<'add_ui>>>,
-    label: Option<Box<AddUi<'add_ui>>>,
-    context_menu: Option<Box<AddUi<'add_ui>>>,
+    icon: Option<Box<AddUiWith<'add_ui, Ctx>>>,
+    closer: Option<Box<AddCloserWith<'add_ui, Ctx>>>,
+    label: Option<Box<AddUiWith<'add_ui, Ctx>>>,
+    collapsed_summary: Option<Box<AddUiWith<'add_ui, Ctx>>>,
+    gutter: Option<Box<AddUiWith<'add_ui, Ctx>>>,
+    context_menu: Option<Box<AddUiWith<'add_ui, Ctx>>>,
 }
-impl<'add_ui, NodeIdType: TreeViewId> NodeBuilder<'add_ui, NodeIdType> {
+impl<'add_ui, NodeIdType: TreeViewId, Ctx> NodeBuilder<'add_ui, NodeIdType, Ctx> {
     /// Create a new node builder from a leaf prototype.
     pub fn leaf(id: NodeIdType) -> Self {
         Self {
@@ -29,9 +58,26 @@ impl<'add_ui, NodeIdType: TreeViewId> NodeBuilder<'add_ui, NodeIdType> {
             is_dir: false,
             flatten: false,
             drop_allowed: false,
+            allowed_drop_positions: DropPositionMask::ALL,
+            activatable: true,
+            child_page_size: None,
+            visible: true,
+            focusable: true,
+            loading: false,
+            children_hint: None,
+            child_count: None,
+            payload: None,
+            kind: 0,
+            show_closer: true,
+            always_open: false,
+            #[cfg(feature = "icons")]
+            default_icon: None,
+            extra_indent: 0,
             icon: None,
             closer: None,
             label: None,
+            collapsed_summary: None,
+            gutter: None,
             context_menu: None,
             is_open: false,
             default_open: true,
@@ -46,9 +92,26 @@ impl<'add_ui, NodeIdType: TreeViewId> NodeBuilder<'add_ui, NodeIdType> {
             is_dir: true,
             flatten: false,
             drop_allowed: true,
+            allowed_drop_positions: DropPositionMask::ALL,
+            activatable: false,
+            child_page_size: None,
+            visible: true,
+            focusable: true,
+            loading: false,
+            children_hint: None,
+            child_count: None,
+            payload: None,
+            kind: 0,
+            show_closer: true,
+            always_open: false,
+            #[cfg(feature = "icons")]
+            default_icon: None,
+            extra_indent: 0,
             icon: None,
             closer: None,
             label: None,
+            collapsed_summary: None,
+            gutter: None,
             context_menu: None,
             is_open: false,
             default_open: true,
@@ -72,44 +135,276 @@ impl<'add_ui, NodeIdType: TreeViewId> NodeBuilder<'add_ui, NodeIdType> {
     }
 
     /// Whether or not dropping onto this node is allowed.
+    ///
+    /// Dropping onto a leaf results in a [`DropPosition::On`](crate::DropPosition::On)
+    /// drop position.
     pub fn drop_allowed(mut self, drop_allowed: bool) -> Self {
         self.drop_allowed = drop_allowed;
         self
     }
 
+    /// Set which drop positions this node accepts.
+    ///
+    /// [`DropPositionMask::INTO`] allows dropping into a directory or onto a leaf.
+    /// [`DropPositionMask::BEFORE`] and [`DropPositionMask::AFTER`] allow dropping
+    /// before or after the children of a directory.
+    ///
+    /// Default is [`DropPositionMask::ALL`].
+    pub fn allowed_drop_positions(mut self, allowed_drop_positions: DropPositionMask) -> Self {
+        self.allowed_drop_positions = allowed_drop_positions;
+        self
+    }
+
+    /// Whether or not this node can be activated.
+    ///
+    /// An activatable node creates an [`Action::Activate`](crate::Action::Activate)
+    /// when it is triggered as configured by [`TreeView::activate_on`](crate::TreeView::activate_on).
+    ///
+    /// Default is `true` for leaves and `false` for directories.
+    pub fn activatable(mut self, activatable: bool) -> Self {
+        self.activatable = activatable;
+        self
+    }
+
+    /// Show the children of this directory in pages of `page_size` nodes.
+    ///
+    /// Only the first page of children is shown. A row at the end of the
+    /// directory reveals the next page when clicked.
+    pub fn child_page_size(mut self, page_size: usize) -> Self {
+        self.child_page_size = Some(page_size.max(1));
+        self
+    }
+
+    /// The number of children of this directory.
+    ///
+    /// With a [`child_page_size`](Self::child_page_size), this allows to stop adding
+    /// children once the shown pages are full while the row at the end of the directory
+    /// still knows how many children are left.
+    /// See [`TreeViewBuilder::is_page_full`](crate::TreeViewBuilder::is_page_full).
+    pub fn child_count(mut self, count: usize) -> Self {
+        self.child_count = Some(count);
+        self
+    }
+
+    /// The number of descendants of this directory in a flat list of nodes.
+    ///
+    /// With this hint, [`TreeViewBuilder::flat_node`](crate::TreeViewBuilder::flat_node)
+    /// returns how many of the following nodes can be skipped when the children
+    /// of the directory are not shown.
+    pub fn children_hint(mut self, count: usize) -> Self {
+        self.children_hint = Some(count);
+        self
+    }
+
+    /// Whether or not this directory shows a closer.
+    ///
+    /// A directory without a closer cannot be opened or closed by the user or by
+    /// restoring a snapshot. It keeps the openness it had when it was first added.
+    pub fn show_closer(mut self, show_closer: bool) -> Self {
+        self.show_closer = show_closer;
+        self
+    }
+
+    /// Whether or not this directory is always open.
+    ///
+    /// An always open directory cannot be closed by the user or by restoring
+    /// a snapshot. Its closer is shown disabled.
+    pub fn always_open(mut self, always_open: bool) -> Self {
+        self.always_open = always_open;
+        self
+    }
+
+    /// Whether the openness of this directory cannot be changed.
+    pub(crate) fn openness_locked(&self) -> bool {
+        !self.show_closer || self.always_open
+    }
+
+    /// Tag this node with a kind.
+    ///
+    /// The kinds of the nodes an action refers to are available with
+    /// [`TreeViewResponse::kinds`](crate::TreeViewResponse::kinds) so the action can be handled
+    /// without looking up the node. Defaults to `0`.
+    pub fn kind(mut self, kind: u32) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Attach a value to this node for the current frame.
+    ///
+    /// The value can be retrieved with [`TreeViewResponse::payload`](crate::TreeViewResponse::payload)
+    /// to react to the actions of the node without looking up its data.
+    pub fn payload<T: Any>(mut self, value: T) -> Self {
+        self.payload = Some(Box::new(value));
+        self
+    }
+
+    /// Whether or not the node is shown.
+    ///
+    /// A node that is not visible is not shown together with all its children.
+    /// Unlike not adding the node at all, its state (e.g. if it is open) is kept.
+    pub fn visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    /// Whether or not the children of this directory are still being loaded.
+    ///
+    /// A loading directory shows a spinner below its children while it is open.
+    pub fn loading(mut self, loading: bool) -> Self {
+        self.loading = loading;
+        self
+    }
+
+    /// Whether or not the node can be navigated to with the keyboard.
+    ///
+    /// Keyboard navigation skips nodes that are not focusable.
+    /// They can still be selected by clicking them.
+    pub fn focusable(mut self, focusable: bool) -> Self {
+        self.focusable = focusable;
+        self
+    }
+
+    /// Indent this node by additional levels.
+    ///
+    /// This only changes how the node is drawn. Its children are not affected.
+    pub fn extra_indent(mut self, extra_indent: usize) -> Self {
+        self.extra_indent = extra_indent;
+        self
+    }
+
     /// Add a icon to the node.
-    pub fn icon(
-        mut self,
-        add_icon: impl FnMut(&mut Ui) + 'add_ui,
-    ) -> NodeBuilder<'add_ui, NodeIdType> {
+    pub fn icon(self, mut add_icon: impl FnMut(&mut Ui) + 'add_ui) -> Self {
+        self.icon_with(move |ui, _| add_icon(ui))
+    }
+
+    /// Show one of the default icons for this node.
+    ///
+    /// Replaces any icon that was set with [`Self::icon`].
+    #[cfg(feature = "icons")]
+    pub fn default_icon(mut self, kind: crate::icons::IconKind) -> Self {
+        self.default_icon = Some(kind);
+        self.icon = None;
+        self
+    }
+
+    /// Show a text as the icon of the node, e.g. an emoji.
+    ///
+    /// The text is centered in the space of the icon.
+    pub fn icon_text(self, text: impl Into<WidgetText>) -> Self {
+        let text = text.into();
+        self.icon(move |ui| {
+            let galley = text.clone().into_galley(
+                ui,
+                Some(TextWrapMode::Extend),
+                f32::INFINITY,
+                TextStyle::Body,
+            );
+            let pos = ui.max_rect().center() - galley.size() * 0.5;
+            ui.painter().galley(
+                pos,
+                galley,
+                ui.visuals().widgets.noninteractive.fg_stroke.color,
+            );
+        })
+    }
+
+    /// Show a single character in the given color as the icon of the node.
+    ///
+    /// The character is sized to fill the space of the icon.
+    pub fn icon_char(self, c: char, color: Color32) -> Self {
+        self.icon(move |ui| {
+            let rect = ui.max_rect();
+            ui.painter().text(
+                rect.center(),
+                Align2::CENTER_CENTER,
+                c,
+                FontId::proportional(rect.height()),
+                color,
+            );
+        })
+    }
+
+    /// Add a icon to the node that has access to the context of the tree view.
+    pub fn icon_with(mut self, add_icon: impl FnMut(&mut Ui, &mut Ctx) + 'add_ui) -> Self {
         self.icon = Some(Box::new(add_icon));
+        #[cfg(feature = "icons")]
+        {
+            self.default_icon = None;
+        }
         self
     }
 
     /// Add a custom closer to the directory node.
     /// Leaves do not show a closer.
-    pub fn closer(
+    pub fn closer(self, mut add_closer: impl FnMut(&mut Ui, CloserState) + 'add_ui) -> Self {
+        self.closer_with(move |ui, state, _| add_closer(ui, state))
+    }
+
+    /// Add a custom closer to the directory node that has access to the
+    /// context of the tree view.
+    /// Leaves do not show a closer.
+    pub fn closer_with(
         mut self,
-        add_closer: impl FnMut(&mut Ui, CloserState) + 'add_ui,
-    ) -> NodeBuilder<'add_ui, NodeIdType> {
+        add_closer: impl FnMut(&mut Ui, CloserState, &mut Ctx) + 'add_ui,
+    ) -> Self {
         self.closer = Some(Box::new(add_closer));
         self
     }
 
     /// Add a label to this node.
-    pub fn label(
-        mut self,
-        add_label: impl FnMut(&mut Ui) + 'add_ui,
-    ) -> NodeBuilder<'add_ui, NodeIdType> {
+    pub fn label(self, mut add_label: impl FnMut(&mut Ui) + 'add_ui) -> Self {
+        self.label_with(move |ui, _| add_label(ui))
+    }
+
+    /// Add a label to this node that has access to the context of the tree view.
+    pub fn label_with(mut self, add_label: impl FnMut(&mut Ui, &mut Ctx) + 'add_ui) -> Self {
         self.label = Some(Box::new(add_label));
         self
     }
 
+    /// Add a summary that is shown after the label while the directory is closed.
+    ///
+    /// Use this to tell something about the hidden children, e.g. "12 items".
+    /// The summary is shown with the weak text color.
+    pub fn collapsed_summary_ui(self, mut add_summary: impl FnMut(&mut Ui) + 'add_ui) -> Self {
+        self.collapsed_summary_ui_with(move |ui, _| add_summary(ui))
+    }
+
+    /// Add a summary that is shown after the label while the directory is closed
+    /// and has access to the context of the tree view.
+    pub fn collapsed_summary_ui_with(
+        mut self,
+        add_summary: impl FnMut(&mut Ui, &mut Ctx) + 'add_ui,
+    ) -> Self {
+        self.collapsed_summary = Some(Box::new(add_summary));
+        self
+    }
+
+    /// Add widgets to the gutter of this node.
+    ///
+    /// The gutter is only shown if the tree has one, see
+    /// [`TreeView::gutter_width`](crate::TreeView::gutter_width).
+    pub fn gutter_ui(self, mut add_gutter: impl FnMut(&mut Ui) + 'add_ui) -> Self {
+        self.gutter_ui_with(move |ui, _| add_gutter(ui))
+    }
+
+    /// Add widgets to the gutter of this node that have access to the
+    /// context of the tree view.
+    pub fn gutter_ui_with(mut self, add_gutter: impl FnMut(&mut Ui, &mut Ctx) + 'add_ui) -> Self {
+        self.gutter = Some(Box::new(add_gutter));
+        self
+    }
+
     /// Add a context menu to this node.
-    pub fn context_menu(
+    pub fn context_menu(self, mut add_context_menu: impl FnMut(&mut Ui) + 'add_ui) -> Self {
+        self.context_menu_with(move |ui, _| add_context_menu(ui))
+    }
+
+    /// Add a context menu to this node that has access to the context of the tree view.
+    pub fn context_menu_with(
         mut self,
-        add_context_menu: impl FnMut(&mut Ui) + 'add_ui,
-    ) -> NodeBuilder<'add_ui, NodeIdType> {
+        add_context_menu: impl FnMut(&mut Ui, &mut Ctx) + 'add_ui,
+    ) -> Self {
         self.context_menu = Some(Box::new(add_context_menu));
         self
     }
@@ -122,12 +417,25 @@ impl<'add_ui, NodeIdType: TreeViewId> NodeBuilder<'add_ui, NodeIdType> {
         self.indent = indent;
     }
 
-    pub(crate) fn show_node(
+    /// Show the row of the node.
+    ///
+    /// The tree can have a different id type than the node. Rows that are not
+    /// nodes of the tree use `()` as their id.
+    pub(crate) fn show_node<TreeIdType: TreeViewId>(
         &mut self,
         ui: &mut Ui,
-        state: &TreeViewData<NodeIdType>,
+        state: &TreeViewData<TreeIdType>,
         settings: &TreeViewSettings,
+        ctx: &mut Ctx,
     ) -> (Rect, Option<Rect>, Option<Rect>, Rect) {
+        // The default icon depends on the openness which is only known now.
+        #[cfg(feature = "icons")]
+        if let Some(kind) = self.default_icon {
+            let open = self.is_dir && self.is_open;
+            self.icon = Some(Box::new(move |ui, _| {
+                crate::icons::paint_icon(ui, kind, open)
+            }));
+        }
         let (reserve_closer, draw_closer, reserve_icon, draw_icon) = match settings.row_layout {
             RowLayout::Compact => (self.is_dir, self.is_dir, false, false),
             RowLayout::CompactAlignedLables => (
@@ -141,112 +449,318 @@ impl<'add_ui, NodeIdType: TreeViewId> NodeBuilder<'add_ui, NodeIdType> {
             }
             RowLayout::AlignedIconsAndLabels => (true, self.is_dir, true, self.icon.is_some()),
         };
+        // Closers on the right of the label do not need space in front of the label.
+        let (reserve_closer, draw_closer) = if settings.show_closers {
+            (
+                reserve_closer && settings.closer_position == CloserPosition::Left,
+                draw_closer && self.show_closer,
+            )
+        } else {
+            (false, false)
+        };
 
+        let row_layout = if settings.right_to_left {
+            Layout::right_to_left(Align::Center)
+        } else {
+            Layout::left_to_right(Align::Center)
+        };
         let InnerResponse {
             inner: (closer, icon, label),
             response: row_response,
-        } = ui.horizontal(|ui| {
-            // The layouting in the row has to be pretty tight so we tunr of the item spacing here.
-            let original_item_spacing = ui.spacing().item_spacing;
-            ui.spacing_mut().item_spacing = Vec2::ZERO;
-
-            ui.add_space(original_item_spacing.x);
-
-            // Add a little space so the closer/icon/label doesnt touch the left side
-            // and add the indentation space.
-            ui.add_space(ui.spacing().item_spacing.x);
-            ui.add_space(
-                self.indent as f32 * settings.override_indent.unwrap_or(ui.spacing().indent),
-            );
-
-            // Draw the closer
-            let closer = draw_closer.then(|| {
-                let (small_rect, big_rect) = ui
-                    .spacing()
-                    .icon_rectangles(ui.available_rect_before_wrap());
-
-                let res = ui.allocate_new_ui(UiBuilder::new().max_rect(big_rect), |ui| {
-                    let closer_interaction = state.interact(&ui.max_rect());
-                    if closer_interaction.hovered {
-                        ui.ctx().set_cursor_icon(CursorIcon::PointingHand);
+        } = ui.allocate_ui_with_layout(
+            vec2(
+                ui.available_size_before_wrap().x,
+                ui.spacing().interact_size.y,
+            ),
+            row_layout,
+            |ui| {
+                // The layouting in the row has to be pretty tight so we tunr of the item spacing here.
+                let original_item_spacing = ui.spacing().item_spacing;
+                ui.spacing_mut().item_spacing = Vec2::ZERO;
+
+                ui.add_space(original_item_spacing.x);
+                if let Some(gutter) = &settings.gutter {
+                    match self.gutter.as_mut() {
+                        Some(add_gutter) => {
+                            ui.allocate_ui_with_layout(
+                                vec2(gutter.width, ui.spacing().interact_size.y),
+                                Layout::left_to_right(Align::Center),
+                                |ui| {
+                                    ui.spacing_mut().item_spacing = original_item_spacing;
+                                    ui.set_min_width(gutter.width);
+                                    add_gutter(ui, ctx);
+                                },
+                            );
+                        }
+                        None => ui.add_space(gutter.width),
                     }
-                    if let Some(add_closer) = self.closer.as_mut() {
-                        (add_closer)(
-                            ui,
-                            CloserState {
-                                is_open: self.is_open,
-                                is_hovered: closer_interaction.hovered,
-                            },
+                }
+
+                // Add a little space so the closer/icon/label doesnt touch the left side
+                // and add the indentation space.
+                ui.add_space(ui.spacing().item_spacing.x);
+                let indent_width = settings.override_indent.unwrap_or(ui.spacing().indent);
+                let indent = self.indent + self.extra_indent;
+                match settings.max_indent_levels {
+                    Some(max_levels) if max_levels > 0 && indent > max_levels => {
+                        // Show that the node is nested deeper than it appears.
+                        ui.add_space((max_levels - 1) as f32 * indent_width);
+                        let (rect, _) = ui.allocate_exact_size(
+                            vec2(indent_width, ui.spacing().interact_size.y),
+                            Sense::hover(),
+                        );
+                        ui.painter().text(
+                            rect.center(),
+                            Align2::CENTER_CENTER,
+                            "…",
+                            TextStyle::Body.resolve(ui.style()),
+                            ui.visuals().weak_text_color(),
                         );
-                    } else {
-                        let icon_id = Id::new(self.id).with("tree view closer icon");
-                        let openness = ui.ctx().animate_bool(icon_id, self.is_open);
-                        let closer_interaction = state.interact(&ui.max_rect());
-                        paint_default_icon(ui, openness, &small_rect, &closer_interaction);
                     }
-                    ui.allocate_space(ui.available_size_before_wrap());
-                });
-                res.response.rect
-            });
-            if closer.is_none() && reserve_closer {
-                ui.add_space(ui.spacing().icon_width);
-            }
-
-            // Draw icon
-            let icon = draw_icon
-                .then(|| {
-                    self.icon.as_mut().map(|add_icon| {
-                        let (_, big_rect) = ui
-                            .spacing()
-                            .icon_rectangles(ui.available_rect_before_wrap());
-                        ui.allocate_new_ui(UiBuilder::new().max_rect(big_rect), |ui| {
-                            ui.set_min_size(big_rect.size());
-                            add_icon(ui);
+                    Some(max_levels) => ui.add_space(indent.min(max_levels) as f32 * indent_width),
+                    None => ui.add_space(indent as f32 * indent_width),
+                }
+
+                // Draw the closer
+                let closer_left = settings.closer_position == CloserPosition::Left;
+                let closer =
+                    (draw_closer && closer_left).then(|| self.closer_ui(ui, state, settings, ctx));
+                if closer.is_none() && reserve_closer {
+                    ui.add_space(ui.spacing().icon_width);
+                }
+
+                // Draw icon
+                let icon = draw_icon
+                    .then(|| {
+                        self.icon.as_mut().map(|add_icon| {
+                            let (_, big_rect) = icon_rectangles(ui);
+                            ui.allocate_new_ui(UiBuilder::new().max_rect(big_rect), |ui| {
+                                ui.set_min_size(big_rect.size());
+                                add_icon(ui, ctx);
+                            })
+                            .response
+                            .rect
                         })
-                        .response
-                        .rect
                     })
-                })
-                .flatten();
-            if icon.is_none() && reserve_icon {
-                ui.add_space(ui.spacing().icon_width);
-            }
-
-            ui.add_space(2.0);
-            // Draw label
-            let label = ui
-                .scope(|ui| {
+                    .flatten();
+                if icon.is_none() && reserve_icon {
+                    ui.add_space(ui.spacing().icon_width);
+                }
+
+                ui.add_space(2.0);
+                // Draw label
+                let closer_width = if draw_closer && !closer_left {
+                    original_item_spacing.x + ui.spacing().icon_width
+                } else {
+                    0.0
+                };
+                // Leave room for the space and the closer after the label.
+                let label_width = ui.available_width() - original_item_spacing.x - closer_width;
+                let mut add_label = |ui: &mut Ui| {
                     ui.spacing_mut().item_spacing = original_item_spacing;
+                    let wrap_mode = if settings.wrap_labels {
+                        Some(egui::TextWrapMode::Wrap)
+                    } else if settings.truncate_labels {
+                        Some(egui::TextWrapMode::Truncate)
+                    } else {
+                        None
+                    };
+                    if let Some(wrap_mode) = wrap_mode {
+                        ui.style_mut().wrap_mode = Some(wrap_mode);
+                        ui.set_max_width(label_width);
+                    }
                     if let Some(add_label) = self.label.as_mut() {
-                        add_label(ui);
+                        add_label(ui, ctx);
                     }
-                })
-                .response
-                .rect;
+                };
+                let label = match settings.label_align {
+                    Some(align) if align != Align::Min => {
+                        // In a right-to-left row the start of the label is on the right.
+                        let align = match align {
+                            Align::Min if settings.right_to_left => Align::Max,
+                            Align::Max if settings.right_to_left => Align::Min,
+                            align => align,
+                        };
+                        // Use the remaining width of the row to align the label in.
+                        let label_width = label_width.at_least(0.0);
+                        ui.allocate_ui_with_layout(
+                            vec2(label_width, 0.0),
+                            Layout::top_down(align),
+                            |ui| {
+                                add_label(ui);
+                                let label = ui.min_rect();
+                                ui.set_min_width(label_width);
+                                label
+                            },
+                        )
+                        .inner
+                    }
+                    _ => ui.scope(add_label).response.rect,
+                };
+
+                // Draw the summary of a closed directory
+                if self.is_dir && !self.is_open {
+                    if let Some(add_summary) = self.collapsed_summary.as_mut() {
+                        ui.add_space(original_item_spacing.x);
+                        ui.scope(|ui| {
+                            ui.spacing_mut().item_spacing = original_item_spacing;
+                            ui.visuals_mut().override_text_color =
+                                Some(ui.visuals().weak_text_color());
+                            add_summary(ui, ctx);
+                        });
+                    }
+                }
 
-            ui.add_space(original_item_spacing.x);
+                let closer = if draw_closer && !closer_left {
+                    ui.add_space(original_item_spacing.x);
+                    Some(self.closer_ui(ui, state, settings, ctx))
+                } else {
+                    closer
+                };
 
-            (closer, icon, label)
-        });
+                ui.add_space(original_item_spacing.x);
+
+                (closer, icon, label)
+            },
+        );
 
         let mut row = row_response
             .rect
             .expand2(vec2(0.0, ui.spacing().item_spacing.y * 0.5));
+        row.min.x = ui.cursor().left();
         row.set_width(ui.available_width());
 
         (row, closer, icon, label)
     }
 
+    /// Draw the closer of the directory.
+    fn closer_ui<TreeIdType: TreeViewId>(
+        &mut self,
+        ui: &mut Ui,
+        state: &TreeViewData<TreeIdType>,
+        settings: &TreeViewSettings,
+        ctx: &mut Ctx,
+    ) -> Rect {
+        let (small_rect, big_rect) = icon_rectangles(ui);
+
+        let res = ui.allocate_new_ui(UiBuilder::new().max_rect(big_rect), |ui| {
+            let mut closer_interaction =
+                state.interact(&ui.max_rect().expand(settings.closer_hit_padding));
+            if self.always_open {
+                ui.disable();
+                closer_interaction.hovered = false;
+            }
+            if closer_interaction.hovered {
+                ui.ctx().set_cursor_icon(CursorIcon::PointingHand);
+            }
+            let visible = match settings.closer_visibility {
+                CloserVisibility::Always => true,
+                CloserVisibility::TreeHovered => state.interaction_response.hovered(),
+                CloserVisibility::RowHovered => {
+                    let row_y = big_rect.y_range().expand(ui.spacing().item_spacing.y * 0.5);
+                    state
+                        .interaction_response
+                        .hover_pos()
+                        .is_some_and(|pos| row_y.contains(pos.y))
+                }
+            };
+            let opacity = if settings.closer_visibility == CloserVisibility::Always {
+                1.0
+            } else if state.animations_enabled {
+                ui.ctx()
+                    .animate_bool(Id::new(self.id).with("tree view closer visible"), visible)
+            } else if visible {
+                1.0
+            } else {
+                0.0
+            };
+            ui.multiply_opacity(opacity);
+            if let Some(add_closer) = self.closer.as_mut() {
+                (add_closer)(
+                    ui,
+                    CloserState {
+                        is_open: self.is_open,
+                        is_hovered: closer_interaction.hovered,
+                    },
+                    ctx,
+                );
+            } else {
+                let openness = if settings.animate_closers && state.animations_enabled {
+                    let icon_id = Id::new(self.id).with("tree view closer icon");
+                    ui.ctx().animate_bool(icon_id, self.is_open)
+                } else if self.is_open {
+                    1.0
+                } else {
+                    0.0
+                };
+                paint_default_icon(
+                    ui,
+                    openness,
+                    &small_rect,
+                    &closer_interaction,
+                    settings.right_to_left,
+                );
+            }
+            ui.allocate_space(ui.available_size_before_wrap());
+        });
+        res.response.rect
+    }
+
     /// Draw the content as a drag overlay if it is beeing dragged.
     pub(crate) fn show_node_dragged(
         &mut self,
         ui: &mut Ui,
         state: &TreeViewData<NodeIdType>,
         settings: &TreeViewSettings,
+        ctx: &mut Ctx,
     ) -> bool {
         ui.ctx().set_cursor_icon(CursorIcon::Alias);
 
         let drag_source_id = ui.make_persistent_id("Drag source");
+        let drag_row_offset = state.peristant.dragged.as_ref().unwrap().drag_row_offset;
+
+        // Outside of the viewport of the tree the content is shown in its own viewport
+        // so that it is not clipped.
+        if settings.drag_overlay_viewport && !ui.ctx().embed_viewports() {
+            let pointer_pos = ui.ctx().pointer_latest_pos();
+            let inner_rect = ui.ctx().input(|i| i.viewport().inner_rect);
+            if let Some((pointer_pos, inner_rect)) = pointer_pos
+                .filter(|pos| !ui.ctx().screen_rect().contains(*pos))
+                .zip(inner_rect)
+            {
+                let row_height = state
+                    .peristant
+                    .row_height_of(self.id)
+                    .unwrap_or(ui.spacing().interact_size.y);
+                let builder = ViewportBuilder::default()
+                    .with_title("Drag overlay")
+                    .with_decorations(false)
+                    .with_transparent(true)
+                    .with_always_on_top()
+                    .with_mouse_passthrough(true)
+                    .with_taskbar(false)
+                    .with_position(inner_rect.min + pointer_pos.to_vec2() + drag_row_offset)
+                    .with_inner_size(vec2(ui.available_width(), row_height));
+                ui.ctx().show_viewport_immediate(
+                    ViewportId::from_hash_of(drag_source_id),
+                    builder,
+                    |egui_ctx, _| {
+                        let frame = Frame::none().fill(
+                            egui_ctx
+                                .style()
+                                .visuals
+                                .selection
+                                .bg_fill
+                                .linear_multiply(0.4),
+                        );
+                        CentralPanel::default().frame(frame).show(egui_ctx, |ui| {
+                            self.show_node(ui, state, settings, ctx);
+                        });
+                    },
+                );
+                return true;
+            }
+        }
 
         // Paint the content to a new layer for the drag overlay.
         let layer_id = LayerId::new(Order::Tooltip, drag_source_id);
@@ -260,7 +774,7 @@ impl<'add_ui, NodeIdType: TreeViewId> NodeBuilder<'add_ui, NodeIdType> {
             .scope_builder(UiBuilder::new().layer_id(layer_id), |ui| {
                 let background_position = ui.painter().add(Shape::Noop);
 
-                let (row, _, _, _) = self.show_node(ui, state, settings);
+                let (row, _, _, _) = self.show_node(ui, state, settings, ctx);
 
                 ui.painter().set(
                     background_position,
@@ -271,6 +785,13 @@ impl<'add_ui, NodeIdType: TreeViewId> NodeBuilder<'add_ui, NodeIdType> {
                         Stroke::NONE,
                     ),
                 );
+
+                if let Some(badge_style) = settings.drag_badge {
+                    // The badge is painted relative to the pointer once the layer
+                    // has been moved to the drag position.
+                    let anchor = row.min - drag_row_offset + vec2(12.0, -12.0);
+                    paint_drag_badge(ui, &badge_style, anchor, state.dragged_node_count);
+                }
                 row
             })
             .inner;
@@ -278,9 +799,7 @@ impl<'add_ui, NodeIdType: TreeViewId> NodeBuilder<'add_ui, NodeIdType> {
         // Move layer to the drag position
         if let Some(pointer_pos) = ui.ctx().pointer_interact_pos() {
             //let delta = -background_rect.min.to_vec2() + pointer_pos.to_vec2() + drag_offset;
-            let delta = -background_rect.min.to_vec2()
-                + pointer_pos.to_vec2()
-                + state.peristant.dragged.as_ref().unwrap().drag_row_offset;
+            let delta = -background_rect.min.to_vec2() + pointer_pos.to_vec2() + drag_row_offset;
             if delta != Vec2::ZERO {
                 let transform = emath::TSTransform::from_translation(delta);
                 ui.ctx().transform_layer_shapes(layer_id, transform);
@@ -290,11 +809,11 @@ impl<'add_ui, NodeIdType: TreeViewId> NodeBuilder<'add_ui, NodeIdType> {
         true
     }
 
-    pub(crate) fn show_context_menu(&mut self, response: &Response) -> bool {
+    pub(crate) fn show_context_menu(&mut self, response: &Response, ctx: &mut Ctx) -> bool {
         if let Some(context_menu) = self.context_menu.as_mut() {
             let mut was_open = false;
             response.context_menu(|ui| {
-                context_menu(ui);
+                context_menu(ui, ctx);
                 was_open = true;
             });
             was_open
@@ -304,12 +823,53 @@ impl<'add_ui, NodeIdType: TreeViewId> NodeBuilder<'add_ui, NodeIdType> {
     }
 }
 
+/// Paint a badge showing the number of dragged nodes.
+fn paint_drag_badge(ui: &mut Ui, style: &DragBadgeStyle, anchor: Pos2, count: usize) {
+    let galley = ui.painter().layout_no_wrap(
+        count.to_string(),
+        FontId::proportional(style.font_size),
+        style
+            .text_color
+            .unwrap_or(ui.visuals().selection.stroke.color),
+    );
+    let height = galley.size().y + 2.0;
+    let rect = Rect::from_min_size(
+        anchor,
+        vec2((galley.size().x + 8.0).at_least(height), height),
+    );
+    ui.painter().rect_filled(
+        rect,
+        height * 0.5,
+        style.fill.unwrap_or(ui.visuals().selection.bg_fill),
+    );
+    ui.painter().galley(
+        rect.center() - galley.size() * 0.5,
+        galley,
+        Color32::PLACEHOLDER,
+    );
+}
+
 /// Paint the arrow icon that indicated if the region is open or not
+/// The rectangles of an icon at the start of the remaining space of a row.
+///
+/// Like [`egui::style::Spacing::icon_rectangles`] but at the right in right-to-left rows.
+fn icon_rectangles(ui: &Ui) -> (Rect, Rect) {
+    let available = ui.available_rect_before_wrap();
+    let (small_rect, big_rect) = ui.spacing().icon_rectangles(available);
+    if ui.layout().prefer_right_to_left() {
+        let offset = vec2(available.right() - big_rect.right(), 0.0);
+        (small_rect.translate(offset), big_rect.translate(offset))
+    } else {
+        (small_rect, big_rect)
+    }
+}
+
 pub(crate) fn paint_default_icon(
     ui: &mut Ui,
     openness: f32,
     rect: &Rect,
     interaction: &Interaction,
+    right_to_left: bool,
 ) {
     let visuals = if interaction.hovered {
         ui.visuals().widgets.hovered
@@ -322,7 +882,9 @@ pub(crate) fn paint_default_icon(
     let rect = rect.expand(visuals.expansion);
     let mut points = vec![rect.left_top(), rect.right_top(), rect.center_bottom()];
     use std::f32::consts::TAU;
-    let rotation = emath::Rot2::from_angle(remap(openness, 0.0..=1.0, -TAU / 4.0..=0.0));
+    // A closed directory points in the direction of the text.
+    let closed_angle = if right_to_left { TAU / 4.0 } else { -TAU / 4.0 };
+    let rotation = emath::Rot2::from_angle(remap(openness, 0.0..=1.0, closed_angle..=0.0));
     for p in &mut points {
         *p = rect.center() + rotation * (*p - rect.center());
     }